@@ -0,0 +1,272 @@
+use crate::cli::{parse_since, parse_timestamp};
+use crate::config::Config;
+use crate::error::{Result, TdError};
+use crate::model::{today_str, write_atomic, Task};
+use crate::task_store::Store;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Which month file each archived task lives in, so single tasks can be found
+// (and new IDs kept unique) without reading every archive
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct ArchiveIndex {
+    #[serde(default)]
+    tasks: BTreeMap<usize, String>,
+}
+
+pub fn get_archive_dir(store: &Store) -> PathBuf {
+    store.sibling_path("archive")
+}
+
+fn get_month_path(store: &Store, month: &str) -> PathBuf {
+    get_archive_dir(store).join(format!("{}.json", month))
+}
+
+fn get_index_path(store: &Store) -> PathBuf {
+    get_archive_dir(store).join("index.json")
+}
+
+// A missing index is rebuilt from the month files, so archived IDs are never
+// forgotten because index.json was lost
+fn load_index(store: &Store) -> Result<ArchiveIndex> {
+    let path = get_index_path(store);
+    if !path.exists() {
+        let tasks = load_archived(store, None, &HashSet::new())?
+            .iter()
+            .map(|t| (t.id, archive_month(t)))
+            .collect();
+        return Ok(ArchiveIndex { tasks });
+    }
+    read_json(&path)
+}
+
+fn load_month(store: &Store, month: &str) -> Result<Vec<Task>> {
+    let path = get_month_path(store, month);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    read_json(&path)
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    serde_json::from_str(&fs::read_to_string(path)?).map_err(|e| TdError::Corrupt {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })
+}
+
+// Day a task was finished; tasks completed before timestamps were recorded use their date
+fn completed_on(t: &Task) -> Option<NaiveDate> {
+    match t.completed_at.as_deref().and_then(parse_timestamp) {
+        Some(at) => Some(at.date_naive()),
+        None => NaiveDate::parse_from_str(&t.date, "%Y-%m-%d").ok(),
+    }
+}
+
+// Archives are split by the month tasks were completed in, e.g. "2025-08"
+fn archive_month(t: &Task) -> String {
+    completed_on(t).map_or_else(|| "undated".to_string(), |d| d.format("%Y-%m").to_string())
+}
+
+// Done tasks finished before `cutoff`. Parents with open subtasks stay so the
+// tree still shows up in `td list`.
+fn archivable(tasks: &[Task], cutoff: NaiveDate) -> Vec<usize> {
+    tasks
+        .iter()
+        .filter(|t| t.done && completed_on(t).is_some_and(|d| d < cutoff))
+        .filter(|t| !tasks.iter().any(|c| c.parent == Some(t.id) && !c.done))
+        .map(|t| t.id)
+        .collect()
+}
+
+// Highest ID ever archived, so new tasks never reuse one
+pub fn max_archived_id(store: &Store) -> Result<usize> {
+    Ok(load_index(store)?
+        .tasks
+        .keys()
+        .next_back()
+        .copied()
+        .unwrap_or(0))
+}
+
+pub fn find_archived_task(store: &Store, id: usize) -> Result<Option<Task>> {
+    let Some(month) = load_index(store)?.tasks.get(&id).cloned() else {
+        return Ok(None);
+    };
+    Ok(load_month(store, &month)?.into_iter().find(|t| t.id == id))
+}
+
+// Archived tasks from month files on or after `since_month` (all of them for `None`),
+// leaving out IDs in `skip`, e.g. tasks that are also still in todo.json
+pub fn load_archived(
+    store: &Store,
+    since_month: Option<&str>,
+    skip: &HashSet<usize>,
+) -> Result<Vec<Task>> {
+    let dir = get_archive_dir(store);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut months: Vec<String> = fs::read_dir(&dir)?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().to_string_lossy().into_owned();
+            let month = name.strip_suffix(".json")?.to_string();
+            (month != "index").then_some(month)
+        })
+        .filter(|month| since_month.is_none_or(|since| month.as_str() >= since))
+        .collect();
+    months.sort();
+
+    let mut tasks = Vec::new();
+    for month in months {
+        tasks.extend(
+            load_month(store, &month)?
+                .into_iter()
+                .filter(|t| !skip.contains(&t.id)),
+        );
+    }
+    Ok(tasks)
+}
+
+// Append tasks to their month files and the index. A task that is already
+// archived is replaced rather than duplicated.
+fn write_archive(store: &Store, tasks: &[Task]) -> Result<Vec<String>> {
+    let mut by_month: BTreeMap<String, Vec<&Task>> = BTreeMap::new();
+    for t in tasks {
+        by_month.entry(archive_month(t)).or_default().push(t);
+    }
+
+    let mut index = load_index(store)?;
+    for (month, new_tasks) in &by_month {
+        let ids: HashSet<usize> = new_tasks.iter().map(|t| t.id).collect();
+        let mut archived = load_month(store, month)?;
+        archived.retain(|t| !ids.contains(&t.id));
+        archived.extend(new_tasks.iter().map(|t| (*t).clone()));
+        archived.sort_by_key(|t| t.id);
+        write_atomic(
+            &get_month_path(store, month),
+            serde_json::to_string_pretty(&archived)?.as_bytes(),
+        )?;
+        for id in ids {
+            index.tasks.insert(id, month.clone());
+        }
+    }
+    // The index is written last; tasks it doesn't know about are still found by `load_archived`
+    write_atomic(
+        &get_index_path(store),
+        serde_json::to_string_pretty(&index)?.as_bytes(),
+    )?;
+    Ok(by_month.into_keys().collect())
+}
+
+// Move done tasks completed before `before` (30d, 2w, a date, ...) out of
+// todo.json; returns how many were moved and the month files they went to
+fn archive_before(
+    store: &mut Store,
+    before: &str,
+    config: &Config,
+) -> Result<(usize, Vec<String>)> {
+    let today_date = NaiveDate::parse_from_str(&today_str(), "%Y-%m-%d")?;
+    let cutoff = parse_since(before, today_date, config.week_start())?;
+    let ids: HashSet<usize> = archivable(store.tasks(), cutoff).into_iter().collect();
+    if ids.is_empty() {
+        return Ok((0, Vec::new()));
+    }
+
+    let moved: Vec<Task> = store
+        .tasks()
+        .iter()
+        .filter(|t| ids.contains(&t.id))
+        .cloned()
+        .collect();
+    // Written before the tasks leave todo.json, so a failure can't lose them
+    let months = write_archive(store, &moved)?;
+    store.archive_tasks(&ids);
+    Ok((moved.len(), months))
+}
+
+pub fn archive(store: &mut Store, before: Option<String>, config: &Config) -> Result<()> {
+    match archive_before(store, before.as_deref().unwrap_or("30d"), config)? {
+        (0, _) => println!("No completed tasks to archive."),
+        (count, months) => {
+            let files: Vec<String> = months
+                .iter()
+                .map(|m| format!("archive/{}.json", m))
+                .collect();
+            println!("[📦] Archived {} task(s) into {}.", count, files.join(", "));
+        }
+    }
+    Ok(())
+}
+
+// The `archive_after` policy from config.toml, applied quietly before commands
+// that modify tasks
+pub fn auto_archive(store: &mut Store, after: &str, config: &Config) -> Result<()> {
+    archive_before(store, after, config).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archivable() {
+        let task = |id: usize, done: bool, completed_at: Option<&str>| Task {
+            id,
+            task: format!("Task {}", id),
+            date: "2025-06-01".to_string(),
+            done,
+            completed_at: completed_at.map(str::to_string),
+            ..Default::default()
+        };
+        let parent = task(4, true, Some("2025-06-02T10:00:00+00:00"));
+        let mut open_child = task(5, false, None);
+        open_child.parent = Some(4);
+        let tasks = vec![
+            task(1, true, Some("2025-06-02T10:00:00+00:00")),
+            task(2, true, Some("2025-07-20T10:00:00+00:00")),
+            task(3, false, None),
+            parent,
+            open_child,
+            // No completion time recorded, so its date counts
+            task(6, true, None),
+        ];
+        let cutoff = NaiveDate::from_ymd_opt(2025, 7, 1).unwrap();
+
+        assert_eq!(archivable(&tasks, cutoff), vec![1, 6]);
+        assert_eq!(archive_month(&tasks[1]), "2025-07");
+        assert_eq!(archive_month(&tasks[5]), "2025-06");
+    }
+
+    #[test]
+    fn test_max_archived_id() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let store = Store::open(temp_dir.path().join("todo.json")).unwrap();
+        assert_eq!(max_archived_id(&store).unwrap(), 0);
+
+        let task = |id: usize| Task {
+            id,
+            task: format!("Task {}", id),
+            date: "2025-06-01".to_string(),
+            done: true,
+            ..Default::default()
+        };
+        write_archive(&store, &[task(3), task(7)]).unwrap();
+        assert_eq!(max_archived_id(&store).unwrap(), 7);
+
+        // A lost index is rebuilt from the month files
+        fs::remove_file(get_index_path(&store)).unwrap();
+        assert_eq!(max_archived_id(&store).unwrap(), 7);
+        assert!(find_archived_task(&store, 3).unwrap().is_some());
+
+        // A damaged one is reported instead of counting from zero
+        fs::write(get_index_path(&store), "{ not json").unwrap();
+        assert!(matches!(
+            max_archived_id(&store),
+            Err(TdError::Corrupt { .. })
+        ));
+    }
+}
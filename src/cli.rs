@@ -1,22 +1,97 @@
+use crate::archive;
+use crate::config::{Config, ListView};
+use crate::dates::{format_minutes, parse_duration_minutes, parse_natural_date, parse_time_of_day};
+use crate::error::{Result, TdError};
+use crate::journal::{load_journal, save_journal, Change, JournalEntry};
 use crate::model::*;
-use crate::task_store::TaskStore;
-use anyhow::Result;
+use crate::prompt_cache;
+use crate::storage::StorageBackend;
+use crate::task_store::Store;
 use chrono::Datelike;
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 use colored::*;
+use std::io::Write;
+
+// Prompt icons flag pending tasks due within this many minutes
+const URGENT_MINUTES: i64 = 30;
+
+// "14:30" or "14:30-15:15" when the task has a duration
+pub(crate) fn format_time_slot(t: &Task) -> Option<String> {
+    let start = chrono::NaiveTime::parse_from_str(t.due_time.as_deref()?, "%H:%M").ok()?;
+    Some(match t.duration {
+        Some(minutes) => {
+            let end = start + chrono::Duration::minutes(minutes as i64);
+            format!("{}-{}", t.due_time.as_deref()?, end.format("%H:%M"))
+        }
+        None => start.format("%H:%M").to_string(),
+    })
+}
+
+// Minutes from `now` until a pending task's due time; negative once it has passed
+pub(crate) fn minutes_until_due(t: &Task, now: chrono::NaiveDateTime) -> Option<i64> {
+    if t.done {
+        return None;
+    }
+    let date = safe_parse_date_str(&t.date)?;
+    let time = chrono::NaiveTime::parse_from_str(t.due_time.as_deref()?, "%H:%M").ok()?;
+    Some((date.and_time(time) - now).num_minutes())
+}
 
-fn get_task_extra_info(t: &Task, today_date: chrono::NaiveDate) -> String {
+// "due in 25m", "in progress" (within its duration) or "late by 1h"
+pub(crate) fn due_status(t: &Task, now: chrono::NaiveDateTime) -> Option<String> {
+    let minutes = minutes_until_due(t, now)?;
+    // Tasks on earlier days are already reported as overdue
+    if safe_parse_date_str(&t.date)? != now.date() {
+        return None;
+    }
+    Some(if minutes > 0 {
+        format!("due in {}", format_minutes(minutes))
+    } else if t.duration.is_some_and(|d| -minutes < d as i64) {
+        "in progress".to_string()
+    } else {
+        format!("late by {}", format_minutes(-minutes))
+    })
+}
+
+fn get_task_extra_info(store: &Store, t: &Task, today_date: chrono::NaiveDate) -> String {
     let mut parts = Vec::new();
+    let tasks = store.tasks();
+
+    // Subtask progress, e.g. "2/5"
+    let children: Vec<&Task> = tasks.iter().filter(|c| c.parent == Some(t.id)).collect();
+    if !children.is_empty() {
+        let done = children.iter().filter(|c| c.done).count();
+        parts.push(format!("{}/{}", done, children.len()));
+    }
+
+    let blockers = t.open_blockers(tasks);
+    if !t.done && !blockers.is_empty() {
+        parts.push(format!("blocked by {}", format_id_list(&blockers)));
+    }
 
     // Add "reused from" part first
     if let Some(reuse_id) = t.reuse_by {
         parts.push(format!("reused from #{}", reuse_id));
     }
 
+    if let Some(ref rule) = t.recurrence {
+        parts.push(format!("every {}", rule));
+    }
+
+    if let Some(slot) = format_time_slot(t) {
+        parts.push(slot);
+    }
+    let now = chrono::Local::now().naive_local();
+    if now.date() == today_date {
+        if let Some(status) = due_status(t, now) {
+            parts.push(status);
+        }
+    }
+
     // Determine the date to use for overdue calculation
     let date_for_overdue_check = if let Some(reuse_id) = t.reuse_by {
         // If it's a reused task, check the original task's overdue status
-        if let Ok(Some(original_task)) = TaskStore::find_task_by_id(reuse_id) {
+        if let Some(original_task) = find_task_or_archived(store, reuse_id) {
             safe_parse_date_str(&original_task.date).unwrap_or(today_date)
         } else {
             // Original task not found, fall back to current task's date
@@ -42,14 +117,127 @@ fn get_task_extra_info(t: &Task, today_date: chrono::NaiveDate) -> String {
     }
 }
 
-fn list_by_date(tasks: &[Task], today_date: NaiveDate, specific_date_str: &str) {
-    let specific_date = match parse_date_str(specific_date_str) {
-        Ok(date) => date,
-        Err(_) => {
-            eprintln!("Error: Invalid date format. Use YYYY-MM-DD.");
+// A task from todo.json, or from the archive once it has been moved there
+fn find_task_or_archived(store: &Store, id: usize) -> Option<Task> {
+    match store.find(id) {
+        Some(task) => Some(task.clone()),
+        None => archive::find_archived_task(store, id).ok().flatten(),
+    }
+}
+
+// Fail before anything is changed when one of `ids` doesn't exist, so a
+// bulk command either applies to every task or to none
+fn ensure_tasks_exist(store: &Store, ids: &[usize]) -> Result<()> {
+    match ids.iter().find(|&&id| store.find(id).is_none()) {
+        Some(&id) => Err(TdError::NotFound(id)),
+        None => Ok(()),
+    }
+}
+
+// IDs keep counting past archived tasks, so `reuse_by` links stay unambiguous
+fn next_task_id(store: &Store) -> Result<usize> {
+    Ok(store.max_id().max(archive::max_archived_id(store)?) + 1)
+}
+
+// Print tasks with subtasks indented under their parent. Tasks whose parent
+// isn't part of `tasks` are shown at the top level; the order is otherwise kept.
+fn print_task_tree(store: &Store, tasks: &[&Task], today_date: NaiveDate, show_times: bool) {
+    fn visit(
+        store: &Store,
+        t: &Task,
+        depth: usize,
+        tasks: &[&Task],
+        printed: &mut std::collections::HashSet<usize>,
+        today_date: NaiveDate,
+        show_times: bool,
+    ) {
+        if !printed.insert(t.id) {
             return;
         }
+        print_task_row(store, t, depth, today_date, show_times);
+        for child in tasks.iter().filter(|c| c.parent == Some(t.id)) {
+            visit(
+                store,
+                child,
+                depth + 1,
+                tasks,
+                printed,
+                today_date,
+                show_times,
+            );
+        }
+    }
+
+    let ids: std::collections::HashSet<usize> = tasks.iter().map(|t| t.id).collect();
+    let mut printed = std::collections::HashSet::new();
+    for t in tasks {
+        if t.parent.is_none_or(|p| !ids.contains(&p)) {
+            visit(store, t, 0, tasks, &mut printed, today_date, show_times);
+        }
+    }
+    // Anything left over is part of a parent cycle; print it flat
+    for t in tasks {
+        visit(store, t, 0, tasks, &mut printed, today_date, show_times);
+    }
+}
+
+fn print_task_row(store: &Store, t: &Task, depth: usize, today_date: NaiveDate, show_times: bool) {
+    let status = if t.done {
+        "[✓]".green().to_string()
+    } else {
+        "[ ]".red().to_string()
     };
+    let mut extra_info = get_task_extra_info(store, t, today_date);
+    if show_times {
+        let times = get_task_times(t);
+        if !times.is_empty() {
+            extra_info.push_str(&format!(" {}", times.dimmed()));
+        }
+    }
+    let indent = if depth > 0 {
+        format!("{}└ ", "  ".repeat(depth - 1))
+    } else {
+        "".to_string()
+    };
+    // Format the output with better alignment - include extra info on same line
+    if !extra_info.is_empty() {
+        println!(
+            "{:>4} {} {}{} {}",
+            t.id,
+            status,
+            indent,
+            format_task_label(t),
+            extra_info
+        );
+    } else {
+        println!("{:>4} {} {}{}", t.id, status, indent, format_task_label(t));
+    }
+}
+
+// "[created 2025-08-01 09:30, done 2025-08-02 17:05]" for `list --times`
+fn get_task_times(t: &Task) -> String {
+    let mut parts = Vec::new();
+    if let Some(created) = t.created_at.as_deref().and_then(format_timestamp) {
+        parts.push(format!("created {}", created));
+    }
+    if let Some(completed) = t.completed_at.as_deref().and_then(format_timestamp) {
+        parts.push(format!("done {}", completed));
+    }
+    if parts.is_empty() {
+        "".to_string()
+    } else {
+        format!("[{}]", parts.join(", "))
+    }
+}
+
+fn list_by_date(
+    store: &Store,
+    tasks: &[Task],
+    today_date: NaiveDate,
+    specific_date: NaiveDate,
+    heading: &str,
+    show_times: bool,
+) {
     let tasks_to_display: Vec<&Task> = tasks
         .iter()
         .filter(|t| {
@@ -60,210 +248,502 @@ fn list_by_date(tasks: &[Task], today_date: NaiveDate, specific_date_str: &str)
             }
         })
         .collect();
-    println!("--- For {} ---", specific_date_str);
-    for t in tasks_to_display {
-        let status = if t.done {
-            "[✓]".green().to_string()
-        } else {
-            "[ ]".red().to_string()
-        };
-        let extra_info = get_task_extra_info(t, today_date);
-        // Format the output with better alignment - include extra info on same line
-        if !extra_info.is_empty() {
-            println!("{:>4} {} {} {}", t.id, status, t.task, extra_info);
-        } else {
-            println!("{:>4} {} {}", t.id, status, t.task);
-        }
-    }
+    println!("--- For {} ---", heading);
+    print_task_tree(store, &tasks_to_display, today_date, show_times);
 }
 
-fn list_by_month(tasks: &[Task], today_date: NaiveDate) {
-    let (month_start, month_end) = get_current_month_range(today_date);
-    let tasks_to_display: Vec<&Task> = tasks
+// Tasks whose date falls within [start, end]; open bounds are unlimited
+fn list_by_range(
+    store: &Store,
+    tasks: &[Task],
+    today_date: NaiveDate,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    heading: &str,
+    show_times: bool,
+) {
+    let mut tasks_to_display: Vec<&Task> = tasks
         .iter()
         .filter(|t| {
             if let Ok(task_date) = parse_date_str(&t.date) {
-                task_date >= month_start && task_date <= month_end
+                start.is_none_or(|s| task_date >= s) && end.is_none_or(|e| task_date <= e)
             } else {
                 false // Exclude tasks with invalid dates
             }
         })
         .collect();
-    println!("--- For Current Month ({}) ---", today_date.format("%Y-%m"));
-    for t in tasks_to_display {
-        let status = if t.done {
-            "[✓]".green().to_string()
-        } else {
-            "[ ]".red().to_string()
-        };
-        let extra_info = get_task_extra_info(t, today_date);
-        // Format the output with better alignment - include extra info on same line
-        if !extra_info.is_empty() {
-            println!("{:>4} {} {} {}", t.id, status, t.task, extra_info);
-        } else {
-            println!("{:>4} {} {}", t.id, status, t.task);
-        }
-    }
+    tasks_to_display.sort_by_key(|t| t.schedule_key());
+    println!("--- {} ---", heading);
+    print_task_tree(store, &tasks_to_display, today_date, show_times);
 }
 
-pub fn add(task: String, date: Option<String>) -> Result<()> {
-    let new_id = TaskStore::get_max_id()? + 1;
+// Optional fields for `td add`; everything except the description
+#[derive(Debug, Default, Clone)]
+pub struct AddOptions {
+    pub date: Option<String>,
+    pub tags: Vec<String>,
+    pub project: Option<String>,
+    pub priority: Option<String>,
+    pub every: Option<String>,
+    pub parent: Option<String>,
+    pub at: Option<String>,
+    pub duration: Option<String>,
+}
+
+pub fn add(store: &mut Store, task: String, opts: AddOptions, config: &Config) -> Result<()> {
+    let AddOptions {
+        date,
+        tags,
+        project,
+        priority,
+        every,
+        parent,
+        at,
+        duration,
+    } = opts;
+    let new_id = next_task_id(store)?;
+    let (task, mut inline_tags, inline_project) = parse_task_input(&task);
     let date = match date {
-        Some(d) => {
-            // Validate date format before adding
-            if parse_date_str(&d).is_err() {
-                eprintln!("Error: Invalid date format '{}'. Please use YYYY-MM-DD.", d);
-                return Ok(()); // Return Ok to prevent program exit
+        // Resolve relative dates before adding
+        Some(d) => resolve_date_str(&d, config.week_start())?,
+        None => today_str(),
+    };
+    let priority = priority
+        .map(|p| p.parse::<Priority>())
+        .transpose()
+        .map_err(TdError::invalid_input)?;
+    let recurrence = every
+        .map(|r| r.parse::<Recurrence>())
+        .transpose()
+        .map_err(TdError::invalid_input)?;
+    let parent = match parent.map(|p| p.parse::<usize>()).transpose() {
+        Ok(Some(parent_id)) => {
+            if store.find(parent_id).is_none() {
+                return Err(TdError::NotFound(parent_id));
             }
-            d
+            Some(parent_id)
         }
-        None => today_str(),
+        Ok(None) => None,
+        Err(_) => return Err(TdError::invalid_input("parent must be a valid task ID")),
     };
+    let due_time = at
+        .map(|a| parse_time_of_day(&a))
+        .transpose()?
+        .map(|t| t.format("%H:%M").to_string());
+    let duration = duration.map(|d| parse_duration_minutes(&d)).transpose()?;
+    // Flags take precedence over inline @project; tags from both are merged
+    for tag in tags {
+        if !inline_tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            inline_tags.push(tag);
+        }
+    }
     let task = Task {
         id: new_id,
         task,
         date,
         done: false,
         reuse_by: None,
+        tags: inline_tags,
+        project: project.or(inline_project),
+        priority,
+        recurrence,
+        created_at: Some(now_str()),
+        completed_at: None,
+        notes: None,
+        parent,
+        blocked_by: Vec::new(),
+        due_time,
+        duration,
     };
-    TaskStore::add_task(task)?;
+    store.add(task);
     println!("[+] Added task #{}", new_id);
     Ok(())
 }
 
-pub fn edit(id: usize, new_task: Option<String>, new_date: Option<String>) -> Result<()> {
-    match TaskStore::find_task_by_id(id) {
-        Ok(Some(mut task)) => {
-            let mut changed = false;
+// Fields to change with `td edit`; "none" clears the optional ones
+#[derive(Debug, Default, Clone)]
+pub struct EditOptions {
+    pub task: Option<String>,
+    pub date: Option<String>,
+    pub priority: Option<String>,
+    pub every: Option<String>,
+    pub at: Option<String>,
+    pub duration: Option<String>,
+    // Replaces the task's tags when given; "none" removes them all
+    pub tags: Vec<String>,
+    pub project: Option<String>,
+}
 
-            if let Some(new_task_str) = new_task {
-                task.task = new_task_str;
-                changed = true;
-            }
+pub fn edit(store: &mut Store, id: usize, opts: EditOptions, config: &Config) -> Result<()> {
+    let EditOptions {
+        task: new_task,
+        date: new_date,
+        priority: new_priority,
+        every: new_every,
+        at: new_at,
+        duration: new_duration,
+        tags: new_tags,
+        project: new_project,
+    } = opts;
+    let mut task = store.find(id).cloned().ok_or(TdError::NotFound(id))?;
+    let mut changed = false;
+
+    if let Some(new_task_str) = new_task {
+        task.task = new_task_str;
+        changed = true;
+    }
 
-            if let Some(new_date_str) = new_date {
-                if parse_date_str(&new_date_str).is_ok() {
-                    task.date = new_date_str;
-                    changed = true;
-                } else {
-                    eprintln!(
-                        "Error: Invalid date format '{}'. Please use YYYY-MM-DD.",
-                        new_date_str
-                    );
-                    return Ok(());
+    if let Some(new_date_str) = new_date {
+        task.date = resolve_date_str(&new_date_str, config.week_start())?;
+        changed = true;
+    }
+
+    if let Some(new_priority_str) = new_priority {
+        if new_priority_str.eq_ignore_ascii_case("none") {
+            task.priority = None;
+        } else {
+            let priority = new_priority_str
+                .parse::<Priority>()
+                .map_err(TdError::invalid_input)?;
+            task.priority = Some(priority);
+        }
+        changed = true;
+    }
+
+    if let Some(new_every_str) = new_every {
+        if new_every_str.eq_ignore_ascii_case("none") {
+            task.recurrence = None;
+        } else {
+            let recurrence = new_every_str
+                .parse::<Recurrence>()
+                .map_err(TdError::invalid_input)?;
+            task.recurrence = Some(recurrence);
+        }
+        changed = true;
+    }
+
+    if let Some(new_at_str) = new_at {
+        if new_at_str.eq_ignore_ascii_case("none") {
+            task.due_time = None;
+        } else {
+            let time = parse_time_of_day(&new_at_str)?;
+            task.due_time = Some(time.format("%H:%M").to_string());
+        }
+        changed = true;
+    }
+
+    if let Some(new_duration_str) = new_duration {
+        if new_duration_str.eq_ignore_ascii_case("none") {
+            task.duration = None;
+        } else {
+            task.duration = Some(parse_duration_minutes(&new_duration_str)?);
+        }
+        changed = true;
+    }
+
+    if !new_tags.is_empty() {
+        task.tags.clear();
+        if !matches!(new_tags.as_slice(), [tag] if tag.eq_ignore_ascii_case("none")) {
+            for tag in new_tags {
+                if !task.tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                    task.tags.push(tag);
                 }
             }
+        }
+        changed = true;
+    }
 
-            if changed {
-                TaskStore::update_task(id, task)?;
-                println!("[✓] Task #{} updated.", id);
-            } else {
-                println!("No changes made to task #{}.", id);
-            }
+    if let Some(new_project_str) = new_project {
+        if new_project_str.eq_ignore_ascii_case("none") {
+            task.project = None;
+        } else {
+            task.project = Some(new_project_str);
         }
-        Ok(None) => eprintln!("Task #{} not found.", id),
-        Err(e) => eprintln!("Error retrieving task #{}: {}", id, e),
+        changed = true;
+    }
+
+    if changed {
+        store.update(id, task);
+        println!("[✓] Task #{} updated.", id);
+    } else {
+        println!("No changes made to task #{}.", id);
     }
     Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
-pub fn list(
-    date_arg: Option<String>,
-    show_week: bool,
-    show_month: bool,
-    show_done: bool,
-    show_pending: bool,
-    from_id: Option<usize>,
-    to_id: Option<usize>,
-    search_keyword: Option<String>,
-    json_output: bool,
-) -> Result<()> {
-    let all_tasks = match TaskStore::get_all_tasks() {
-        Ok(tasks) => tasks,
-        Err(e) => {
-            eprintln!("Error retrieving tasks: {}", e);
-            return Ok(());
+// Filters shared by commands that select tasks, e.g. `td list`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TaskFilter {
+    pub date: Option<String>,
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+    // "this" for the current week/month, or an explicit 2025-W31 / 2025-07
+    pub week: Option<String>,
+    pub month: Option<String>,
+    pub last_week: bool,
+    pub next_week: bool,
+    pub overdue: bool,
+    pub show_done: bool,
+    pub show_pending: bool,
+    pub from_id: Option<usize>,
+    pub to_id: Option<usize>,
+    pub search: Option<String>,
+    pub tag: Option<String>,
+    pub project: Option<String>,
+}
+
+// The date selection a filter resolves to, used for both filtering and display
+enum DateWindow {
+    // Today plus unfinished tasks since the start of the week
+    Default {
+        week_start: NaiveDate,
+    },
+    Day(NaiveDate),
+    Range {
+        start: Option<NaiveDate>,
+        end: Option<NaiveDate>,
+        heading: String,
+    },
+}
+
+impl TaskFilter {
+    // Without date flags, the flags for the `default_view` in config.toml.
+    // Views such as "overdue" also narrow which tasks match, so this is
+    // applied to the filter as a whole rather than just its date window.
+    fn with_default_view(&self, config: &Config) -> TaskFilter {
+        let has_dates = self.date.is_some()
+            || self.from_date.is_some()
+            || self.to_date.is_some()
+            || self.week.is_some()
+            || self.month.is_some()
+            || self.last_week
+            || self.next_week
+            || self.overdue;
+        match config.default_view {
+            _ if has_dates => self.clone(),
+            ListView::Week => TaskFilter {
+                week: Some("this".to_string()),
+                ..self.clone()
+            },
+            ListView::Month => TaskFilter {
+                month: Some("this".to_string()),
+                ..self.clone()
+            },
+            ListView::Overdue => TaskFilter {
+                overdue: true,
+                ..self.clone()
+            },
+            ListView::Today | ListView::All => self.clone(),
         }
-    };
+    }
 
-    let today = today_str();
-    let today_date = match parse_date_str(&today) {
-        Ok(date) => date,
-        Err(_) => {
-            eprintln!("Error: Failed to parse today's date");
-            return Ok(());
+    fn date_window(&self, today_date: NaiveDate, config: &Config) -> Result<DateWindow> {
+        let week_range = |anchor| get_week_range(anchor, config.week_start());
+        let fmt = |date| config.format_date(date);
+
+        if let Some(ref date) = self.date {
+            return Ok(DateWindow::Day(parse_date_str(&resolve_date_str(
+                date,
+                config.week_start(),
+            )?)?));
+        }
+
+        if self.from_date.is_some() || self.to_date.is_some() {
+            let start = self
+                .from_date
+                .as_deref()
+                .map(|d| parse_natural_date(d, today_date, config.week_start()))
+                .transpose()?;
+            let end = self
+                .to_date
+                .as_deref()
+                .map(|d| parse_natural_date(d, today_date, config.week_start()))
+                .transpose()?;
+            let heading = match (start, end) {
+                (Some(s), Some(e)) => format!("For {} to {}", fmt(s), fmt(e)),
+                (Some(s), None) => format!("Since {}", fmt(s)),
+                (None, Some(e)) => format!("Until {}", fmt(e)),
+                (None, None) => unreachable!(),
+            };
+            return Ok(DateWindow::Range {
+                start,
+                end,
+                heading,
+            });
+        }
+
+        let week = if self.last_week {
+            Some((
+                week_range(today_date - chrono::Duration::weeks(1)),
+                "Last Week".to_string(),
+            ))
+        } else if self.next_week {
+            Some((
+                week_range(today_date + chrono::Duration::weeks(1)),
+                "Next Week".to_string(),
+            ))
+        } else if let Some(ref week) = self.week {
+            let range = parse_week_arg(week, today_date, config.week_start())?;
+            let name = if range == week_range(today_date) {
+                "Current Week".to_string()
+            } else if range.0.weekday() == chrono::Weekday::Mon {
+                format!("Week {}", range.0.format("%G-W%V"))
+            } else {
+                // Weeks that don't start on Monday have no ISO number
+                "Week".to_string()
+            };
+            Some((range, name))
+        } else {
+            None
+        };
+        if let Some(((week_start, week_end), name)) = week {
+            return Ok(DateWindow::Range {
+                start: Some(week_start),
+                end: Some(week_end),
+                heading: format!("For {} ({} to {})", name, fmt(week_start), fmt(week_end)),
+            });
+        }
+
+        if let Some(ref month) = self.month {
+            let anchor = parse_month_arg(month, today_date, config.week_start())?;
+            let (month_start, month_end) = get_month_range(anchor);
+            let heading = if month_start == get_month_range(today_date).0 {
+                format!("For Current Month ({})", anchor.format("%Y-%m"))
+            } else {
+                format!("For Month {}", anchor.format("%Y-%m"))
+            };
+            return Ok(DateWindow::Range {
+                start: Some(month_start),
+                end: Some(month_end),
+                heading,
+            });
+        }
+
+        if self.overdue {
+            return Ok(DateWindow::Range {
+                start: None,
+                end: today_date.pred_opt(),
+                heading: "Overdue Tasks".to_string(),
+            });
         }
-    };
 
+        // No date flags: fall back to the configured default view
+        match config.default_view {
+            ListView::Today => Ok(DateWindow::Default {
+                week_start: week_range(today_date).0,
+            }),
+            ListView::Week | ListView::Month | ListView::Overdue => self
+                .with_default_view(config)
+                .date_window(today_date, config),
+            ListView::All => Ok(DateWindow::Range {
+                start: None,
+                end: None,
+                heading: "All Tasks".to_string(),
+            }),
+        }
+    }
+}
+
+// The filter pipeline behind `td list`, also used to select tasks for bulk actions
+fn apply_filter(
+    tasks: Vec<Task>,
+    filter: &TaskFilter,
+    window: &DateWindow,
+    today_date: NaiveDate,
+) -> Vec<Task> {
     // Create a mutable copy for filtering
-    let mut all_tasks = all_tasks;
+    let mut all_tasks = tasks;
 
     // Apply status filter if specified
-    if show_done && !show_pending {
+    if filter.show_done && !filter.show_pending {
         all_tasks.retain(|t| t.done);
-    } else if show_pending && !show_done {
+    } else if filter.show_pending && !filter.show_done {
         all_tasks.retain(|t| !t.done);
     }
     // If both flags are true or both are false, show all tasks
 
     // Apply ID range filter if specified
-    if let Some(from) = from_id {
+    if let Some(from) = filter.from_id {
         all_tasks.retain(|t| t.id >= from);
     }
-    if let Some(to) = to_id {
+    if let Some(to) = filter.to_id {
         all_tasks.retain(|t| t.id <= to);
     }
 
     // Apply search filter if specified
-    if let Some(keyword) = search_keyword {
-        all_tasks.retain(|t| t.task.to_lowercase().contains(&keyword));
+    if let Some(ref keyword) = filter.search {
+        all_tasks.retain(|t| t.task.to_lowercase().contains(keyword));
+    }
+
+    // Apply tag and project filters if specified
+    all_tasks
+        .retain(|t| matches_tag_and_project(t, filter.tag.as_deref(), filter.project.as_deref()));
+
+    // Overdue narrows any date window to unfinished tasks dated before today
+    if filter.overdue {
+        all_tasks.retain(|t| {
+            !t.done && parse_date_str(&t.date).is_ok_and(|task_date| task_date < today_date)
+        });
     }
 
-    // Apply date filter if specified
-    if let Some(ref specific_date_str) = date_arg {
-        if let Ok(parsed_date) = parse_date_str(specific_date_str) {
+    // Apply date window
+    match *window {
+        DateWindow::Day(specific_date) => {
             all_tasks.retain(|t| {
                 if let Ok(task_date) = parse_date_str(&t.date) {
-                    task_date == parsed_date
+                    task_date == specific_date
+                } else {
+                    false // Exclude tasks with invalid dates
+                }
+            });
+        }
+        DateWindow::Range { start, end, .. } => {
+            all_tasks.retain(|t| {
+                if let Ok(task_date) = parse_date_str(&t.date) {
+                    start.is_none_or(|s| task_date >= s) && end.is_none_or(|e| task_date <= e)
+                } else {
+                    false // Exclude tasks with invalid dates
+                }
+            });
+        }
+        DateWindow::Default { week_start } => {
+            all_tasks.retain(|t| {
+                if let Ok(task_date) = parse_date_str(&t.date) {
+                    let is_past_undone =
+                        task_date >= week_start && task_date < today_date && !t.done;
+                    let is_today = task_date == today_date;
+                    is_past_undone || is_today
                 } else {
                     false // Exclude tasks with invalid dates
                 }
             });
-        } else {
-            eprintln!(
-                "Error: Invalid date format '{}'. Please use YYYY-MM-DD.",
-                specific_date_str
-            );
-            return Ok(());
         }
     }
 
-    if show_week {
-        let (week_start, week_end) = get_current_week_range(today_date);
-        all_tasks.retain(|t| {
-            if let Ok(task_date) = parse_date_str(&t.date) {
-                task_date >= week_start && task_date <= week_end
-            } else {
-                false // Exclude tasks with invalid dates
-            }
-        });
-    } else if show_month {
-        // For month view, no additional filtering beyond other criteria
-    } else if date_arg.is_none() {
-        // Only apply the default week filter if no other date filter is specified
-        let (week_start, _) = get_current_week_range(today_date);
-        all_tasks.retain(|t| {
-            if let Ok(task_date) = parse_date_str(&t.date) {
-                let is_past_undone = task_date >= week_start && task_date < today_date && !t.done;
-                let is_today = task_date == today_date;
-                is_past_undone || is_today
-            } else {
-                false // Exclude tasks with invalid dates
-            }
-        });
+    all_tasks
+}
+
+// With `archived`, tasks moved out by `td archive` are listed as well
+pub fn list(
+    store: &Store,
+    filter: TaskFilter,
+    json_output: bool,
+    show_times: bool,
+    archived: bool,
+    config: &Config,
+) -> Result<()> {
+    let mut all_tasks = store.tasks().to_vec();
+    if archived {
+        let hot_ids = all_tasks.iter().map(|t| t.id).collect();
+        all_tasks.extend(archive::load_archived(store, None, &hot_ids)?);
     }
 
+    let today_date = parse_date_str(&today_str())?;
+    let filter = filter.with_default_view(config);
+    let window = filter.date_window(today_date, config)?;
+
+    let all_tasks = apply_filter(all_tasks, &filter, &window, today_date);
+
     if json_output {
         // Output tasks as JSON
         println!("{}", serde_json::to_string_pretty(&all_tasks)?);
@@ -271,70 +751,552 @@ pub fn list(
         // Traditional output
         println!("Tasks:");
 
-        if let Some(ref specific_date_str) = date_arg {
-            list_by_date(&all_tasks, today_date, specific_date_str);
-        } else if show_week {
-            list_by_week(&all_tasks, today_date);
-        } else if show_month {
-            list_by_month(&all_tasks, today_date);
-        } else {
-            list_default(&all_tasks, today_date);
+        match window {
+            DateWindow::Day(specific_date) => list_by_date(
+                store,
+                &all_tasks,
+                today_date,
+                specific_date,
+                &config.format_date(specific_date),
+                show_times,
+            ),
+            DateWindow::Range {
+                start,
+                end,
+                ref heading,
+            } => list_by_range(
+                store, &all_tasks, today_date, start, end, heading, show_times,
+            ),
+            DateWindow::Default { week_start } => {
+                list_default(store, &all_tasks, today_date, week_start, show_times)
+            }
         }
     }
 
     Ok(())
 }
 
-pub fn mark_done(id: usize) -> Result<()> {
-    match TaskStore::find_task_by_id(id) {
-        Ok(Some(mut task)) => {
-            task.done = true;
-            TaskStore::update_task(id, task)?;
-            println!("[✓] Task #{} marked done.", id);
+// Completed tasks grouped by the day they were finished, oldest first
+pub fn log(store: &Store, since: Option<String>, json_output: bool, config: &Config) -> Result<()> {
+    let today_date = parse_date_str(&today_str())?;
+    let since_date = parse_since(
+        since.as_deref().unwrap_or("7d"),
+        today_date,
+        config.week_start(),
+    )?;
+
+    // Archives are split by completion month, so older months can be skipped
+    let tasks = store.tasks().to_vec();
+    let hot_ids: std::collections::HashSet<usize> = tasks.iter().map(|t| t.id).collect();
+    let since_month = since_date.format("%Y-%m").to_string();
+    let archived = archive::load_archived(store, Some(&since_month), &hot_ids)?;
+
+    // Tasks finished before timestamps were recorded have no completed_at and are skipped
+    let mut completed: Vec<(chrono::DateTime<chrono::Local>, Task)> = tasks
+        .into_iter()
+        .chain(archived)
+        .filter(|t| t.done)
+        .filter_map(|t| {
+            let at = parse_timestamp(t.completed_at.as_deref()?)?;
+            (at.date_naive() >= since_date).then_some((at, t))
+        })
+        .collect();
+    completed.sort_by_key(|(at, t)| (*at, t.id));
+
+    if json_output {
+        let tasks: Vec<&Task> = completed.iter().map(|(_, t)| t).collect();
+        println!("{}", serde_json::to_string_pretty(&tasks)?);
+        return Ok(());
+    }
+
+    println!("--- Completed Since {} ---", config.format_date(since_date));
+    if completed.is_empty() {
+        println!("No tasks completed in this period.");
+        return Ok(());
+    }
+
+    let mut current_day = None;
+    for (at, t) in &completed {
+        let day = at.date_naive();
+        if current_day != Some(day) {
+            println!(
+                "{}",
+                format!("{} ({})", config.format_date(day), day.format("%a")).bold()
+            );
+            current_day = Some(day);
         }
-        Ok(None) => eprintln!("Task #{} not found.", id),
-        Err(e) => eprintln!("Error retrieving task #{}: {}", id, e),
+        println!(
+            "  {} {:>4} {}",
+            at.format("%H:%M").to_string().dimmed(),
+            t.id,
+            format_task_label(t)
+        );
     }
+    println!("{} task(s) completed.", completed.len());
     Ok(())
 }
 
-pub fn remove(id: usize) -> Result<()> {
-    match TaskStore::remove_task(id) {
-        Ok(success) => {
-            if success {
-                println!("[-] Task #{} removed.", id);
-            } else {
-                eprintln!("Task #{} not found.", id);
+// A parent with open subtasks is only completed when `cascade` is set,
+// in which case the subtasks are completed first
+pub fn mark_done(store: &mut Store, id: usize, cascade: bool) -> Result<()> {
+    let mut task = store.find(id).cloned().ok_or(TdError::NotFound(id))?;
+    let open_subtasks: Vec<usize> = store
+        .tasks()
+        .iter()
+        .filter(|t| t.parent == Some(id) && !t.done)
+        .map(|t| t.id)
+        .collect();
+    if !task.done && !open_subtasks.is_empty() {
+        if !cascade {
+            return Err(TdError::InvalidInput(format!(
+                "Task #{} has {} open subtask(s). Finish them first or use --cascade.",
+                id,
+                open_subtasks.len()
+            )));
+        }
+        for subtask_id in open_subtasks {
+            mark_done(store, subtask_id, true)?;
+        }
+    }
+
+    let blockers = task.open_blockers(store.tasks());
+    if !task.done && !blockers.is_empty() {
+        eprintln!(
+            "Warning: Task #{} is still blocked by open task(s) {}.",
+            id,
+            format_id_list(&blockers)
+        );
+    }
+
+    let was_done = task.done;
+    task.done = true;
+    if !was_done {
+        task.completed_at = Some(now_str());
+    }
+    store.update(id, task.clone());
+    println!("[✓] Task #{} marked done.", id);
+
+    // Completing a recurring task schedules its next occurrence
+    if !was_done {
+        if let Some(ref rule) = task.recurrence {
+            match spawn_next_occurrence(store, &task, rule)? {
+                Some((next_id, next_date)) => println!(
+                    "[↻] Next occurrence scheduled as task #{} on {}.",
+                    next_id, next_date
+                ),
+                None => println!(
+                    "[↻] Task #{} doesn't repeat again; its next occurrence is past the last supported date.",
+                    id
+                ),
             }
         }
-        Err(e) => eprintln!("Error removing task #{}: {}", id, e),
     }
     Ok(())
 }
 
-pub fn prompt_today() -> Result<()> {
-    let all_tasks = match TaskStore::get_all_tasks() {
-        Ok(tasks) => tasks,
-        Err(e) => {
-            eprintln!("Error retrieving tasks: {}", e);
-            return Ok(());
+pub fn remove(store: &mut Store, id: usize) -> Result<()> {
+    let parent = store.find(id).ok_or(TdError::NotFound(id))?.parent;
+    store.remove(id);
+    // Subtasks move up to the removed task's parent and
+    // tasks waiting on it are no longer blocked by it
+    let dependents: Vec<Task> = store
+        .tasks()
+        .iter()
+        .filter(|t| t.parent == Some(id) || t.blocked_by.contains(&id))
+        .cloned()
+        .collect();
+    for mut other in dependents {
+        if other.parent == Some(id) {
+            other.parent = parent;
         }
+        other.blocked_by.retain(|&b| b != id);
+        store.update(other.id, other);
+    }
+    println!("[-] Task #{} removed.", id);
+    Ok(())
+}
+
+// Edit a task's notes in $VISUAL / $EDITOR; saving an empty file clears them.
+// The store is unlocked while the editor is open, so other commands can run.
+pub fn note(store: &mut Store, id: usize) -> Result<()> {
+    let task = store.find(id).cloned().ok_or(TdError::NotFound(id))?;
+
+    let path = std::env::temp_dir().join(format!("td-note-{}-{}.md", id, std::process::id()));
+    std::fs::write(&path, task.notes.as_deref().unwrap_or(""))?;
+    store.unlock()?;
+    let edited = run_editor(&path).and_then(|_| Ok(std::fs::read_to_string(&path)?));
+    let _ = std::fs::remove_file(&path);
+    let edited = edited?;
+
+    // The task may have changed, or gone, while the editor was open
+    store.relock()?;
+    let mut task = store.find(id).cloned().ok_or(TdError::NotFound(id))?;
+
+    let notes = Some(edited.trim_end().to_string()).filter(|n| !n.trim().is_empty());
+    if notes == task.notes {
+        println!("Notes for task #{} unchanged.", id);
+        return Ok(());
+    }
+    let cleared = notes.is_none();
+    task.notes = notes;
+    store.update(id, task);
+    if cleared {
+        println!("[*] Notes for task #{} cleared.", id);
+    } else {
+        println!("[*] Notes for task #{} updated.", id);
+    }
+    Ok(())
+}
+
+// Everything known about one task: metadata, dates, lineage and notes
+pub fn show(store: &Store, id: usize, config: &Config) -> Result<()> {
+    let (task, archived) = match store.find(id) {
+        Some(task) => (task.clone(), false),
+        None => match archive::find_archived_task(store, id)? {
+            Some(task) => (task, true),
+            None => return Err(TdError::NotFound(id)),
+        },
     };
+    let today_date = parse_date_str(&today_str())?;
 
-    let today = today_str();
-    let today_date = match parse_date_str(&today) {
-        Ok(date) => date,
-        Err(_) => {
-            eprintln!("Error: Failed to parse today's date");
+    let status = if task.done {
+        "[✓]".green().to_string()
+    } else {
+        "[ ]".red().to_string()
+    };
+    let archived = if archived {
+        " (archived)".dimmed().to_string()
+    } else {
+        String::new()
+    };
+    println!(
+        "#{} {} {}{}",
+        task.id,
+        status,
+        format_task_label(&task),
+        archived
+    );
+
+    let field = |name: &str, value: String| println!("  {:<11} {}", format!("{}:", name), value);
+    field(
+        "Date",
+        format!(
+            "{}{}",
+            safe_parse_date_str(&task.date).map_or(task.date.clone(), |d| config.format_date(d)),
+            get_task_extra_info(store, &task, today_date)
+        ),
+    );
+    if let Some(priority) = task.priority {
+        field("Priority", format!("{:?}", priority));
+    }
+    if !task.tags.is_empty() {
+        field("Tags", task.tags.join(", "));
+    }
+    if let Some(ref project) = task.project {
+        field("Project", project.clone());
+    }
+    if let Some(ref rule) = task.recurrence {
+        field("Repeats", format!("every {}", rule));
+    }
+    let all_tasks = store.tasks();
+    if let Some(parent_id) = task.parent {
+        let title = all_tasks
+            .iter()
+            .find(|t| t.id == parent_id)
+            .map(|t| t.task.clone())
+            .unwrap_or_default();
+        field("Parent", format!("#{} {}", parent_id, title));
+    }
+    if !task.blocked_by.is_empty() {
+        let blockers: Vec<String> = task
+            .blocked_by
+            .iter()
+            .map(|b| match all_tasks.iter().find(|t| t.id == *b) {
+                Some(t) if t.done => format!("#{} {} (done)", b, t.task),
+                Some(t) => format!("#{} {}", b, t.task),
+                None => format!("#{} (removed)", b),
+            })
+            .collect();
+        field("Blocked by", blockers.join(", "));
+    }
+    let blocks: Vec<usize> = all_tasks
+        .iter()
+        .filter(|t| t.blocked_by.contains(&id))
+        .map(|t| t.id)
+        .collect();
+    if !blocks.is_empty() {
+        field("Blocks", format_id_list(&blocks));
+    }
+    if let Some(created) = task.created_at.as_deref().and_then(format_timestamp) {
+        field("Created", created);
+    }
+    if let Some(completed) = task.completed_at.as_deref().and_then(format_timestamp) {
+        field("Completed", completed);
+    }
+
+    // Reuses and recurrences all point back at the same original task
+    let origin = task.reuse_by.unwrap_or(task.id);
+    let mut lineage: Vec<&Task> = all_tasks
+        .iter()
+        .filter(|t| t.id == origin || t.reuse_by == Some(origin))
+        .collect();
+    lineage.sort_by_key(|t| t.id);
+    if lineage.len() > 1 {
+        let chain: Vec<String> = lineage
+            .iter()
+            .map(|t| {
+                let id = format!("#{}", t.id);
+                if t.id == task.id {
+                    id.bold().to_string()
+                } else {
+                    id
+                }
+            })
+            .collect();
+        field("Lineage", chain.join(" → "));
+    }
+
+    // The whole subtree below this task
+    let mut subtree = std::collections::HashSet::from([id]);
+    loop {
+        let size = subtree.len();
+        for t in all_tasks {
+            if t.parent.is_some_and(|p| subtree.contains(&p)) {
+                subtree.insert(t.id);
+            }
+        }
+        if subtree.len() == size {
+            break;
+        }
+    }
+    let subtasks: Vec<&Task> = all_tasks
+        .iter()
+        .filter(|t| t.id != id && subtree.contains(&t.id))
+        .collect();
+    if !subtasks.is_empty() {
+        println!();
+        println!("{}", "Subtasks:".bold());
+        print_task_tree(store, &subtasks, today_date, false);
+    }
+
+    if let Some(ref notes) = task.notes {
+        println!();
+        println!("{}", "Notes:".bold());
+        for line in notes.lines() {
+            println!("  {}", line);
+        }
+    }
+    Ok(())
+}
+
+// Record that each of `ids` depends on every task in `on`
+pub fn block(store: &mut Store, ids: &[usize], on: &[usize]) -> Result<()> {
+    ensure_tasks_exist(store, on)?;
+    ensure_tasks_exist(store, ids)?;
+    for &id in ids {
+        let mut task = store.find(id).cloned().ok_or(TdError::NotFound(id))?;
+        let mut added = Vec::new();
+        for &blocker in on {
+            if blocker == id || depends_on(store.tasks(), blocker, id) {
+                return Err(TdError::InvalidInput(format!(
+                    "#{} can't wait on #{}; that would create a dependency cycle.",
+                    id, blocker
+                )));
+            }
+            if !task.blocked_by.contains(&blocker) {
+                task.blocked_by.push(blocker);
+                added.push(blocker);
+            }
+        }
+        if !added.is_empty() {
+            store.update(id, task);
+            println!(
+                "[⊘] Task #{} is now blocked by {}.",
+                id,
+                format_id_list(&added)
+            );
+        }
+    }
+    Ok(())
+}
+
+// Remove the given dependencies, or all of them when `on` is empty
+pub fn unblock(store: &mut Store, ids: &[usize], on: &[usize]) -> Result<()> {
+    ensure_tasks_exist(store, ids)?;
+    for &id in ids {
+        let mut task = store.find(id).cloned().ok_or(TdError::NotFound(id))?;
+        let before = task.blocked_by.len();
+        task.blocked_by
+            .retain(|b| !on.is_empty() && !on.contains(b));
+        if task.blocked_by.len() == before {
+            println!("Task #{} has no matching dependencies.", id);
+            continue;
+        }
+        store.update(id, task);
+        println!("[⊘] Removed dependencies from task #{}.", id);
+    }
+    Ok(())
+}
+
+// IDs of the tasks `td list` would show for the same filter
+pub fn select_task_ids(store: &Store, filter: &TaskFilter, config: &Config) -> Result<Vec<usize>> {
+    let today_date = parse_date_str(&today_str())?;
+    let filter = filter.with_default_view(config);
+    let window = filter.date_window(today_date, config)?;
+    let tasks = apply_filter(store.tasks().to_vec(), &filter, &window, today_date);
+    let mut ids: Vec<usize> = tasks.iter().map(|t| t.id).collect();
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+// Print how many tasks `td list` would show for the same flags; only pending
+// tasks are counted unless --done is given
+pub fn count(store: &Store, mut filter: TaskFilter, config: &Config) -> Result<usize> {
+    if !filter.show_done {
+        filter.show_pending = true;
+    }
+    let n = select_task_ids(store, &filter, config)?.len();
+    println!("{}", n);
+    Ok(n)
+}
+
+pub fn mark_done_many(store: &mut Store, ids: &[usize], cascade: bool) -> Result<()> {
+    if ids.is_empty() {
+        println!("No matching tasks.");
+        return Ok(());
+    }
+    ensure_tasks_exist(store, ids)?;
+    // Complete the deepest subtasks first so selected parents aren't refused
+    let mut ordered = ids.to_vec();
+    ordered.sort_by_key(|&id| std::cmp::Reverse(task_depth(store.tasks(), id)));
+    for id in ordered {
+        mark_done(store, id, cascade)?;
+    }
+    if ids.len() > 1 {
+        println!("[✓] {} tasks processed.", ids.len());
+    }
+    Ok(())
+}
+
+pub fn remove_many(store: &mut Store, ids: &[usize], assume_yes: bool) -> Result<()> {
+    if ids.is_empty() {
+        println!("No matching tasks.");
+        return Ok(());
+    }
+
+    ensure_tasks_exist(store, ids)?;
+
+    // Removing more than one task asks for confirmation first
+    if ids.len() > 1 && !assume_yes {
+        println!("The following tasks will be removed:");
+        for t in ids.iter().filter_map(|&id| store.find(id)) {
+            println!("{:>4} {} {}", t.id, t.date, format_task_label(t));
+        }
+        if !confirm(&format!("Remove {} tasks?", ids.len()))? {
+            println!("Aborted. No tasks removed.");
             return Ok(());
         }
-    };
+    }
 
-    let (week_start, _) = get_current_week_range(today_date);
+    for &id in ids {
+        remove(store, id)?;
+    }
+    Ok(())
+}
+
+pub fn edit_many(
+    store: &mut Store,
+    ids: &[usize],
+    opts: EditOptions,
+    config: &Config,
+) -> Result<()> {
+    ensure_tasks_exist(store, ids)?;
+    for &id in ids {
+        edit(store, id, opts.clone(), config)?;
+    }
+    Ok(())
+}
+
+// Placeholder defaults for `td prompt-today`; the output is unchanged unless --format is given
+pub const DEFAULT_PROMPT_FORMAT: &str = "{tasks}";
+pub const DEFAULT_PROMPT_TASK_FORMAT: &str = "{icon} #{id}";
+
+// `format` may use {tasks}, the counts {pending} {done} {overdue} {reused} {high} {total},
+// {next} / {next_id} for the next pending task and the icons {done_icon} {pending_icon}
+// {high_icon} {reused_icon} {overdue_icon}. `task_format` renders each entry of {tasks}
+// from {icon} {id} {task} {date} {time} {priority}.
+//
+// This runs on every prompt render, so the store is only opened (read-only)
+// when the cached output from the last run no longer applies.
+pub fn prompt_today(
+    backend: Box<dyn StorageBackend>,
+    tag: Option<String>,
+    project: Option<String>,
+    format: Option<String>,
+    task_format: Option<String>,
+    config: &Config,
+) -> Result<()> {
+    let now = chrono::Local::now().naive_local();
+    // Everything besides the task file and the date that shapes the output
+    let params = format!(
+        "{:?} {:?} {:?} {:?} {:?}",
+        tag, project, format, task_format, config
+    );
+    let data_path = backend.path().to_path_buf();
+    let key = prompt_cache::cache_key(&data_path, &params, now).ok();
+    if let Some(output) = key
+        .as_ref()
+        .and_then(|key| prompt_cache::lookup(&data_path, key, now))
+    {
+        if !output.is_empty() {
+            println!("{}", output);
+        }
+        return Ok(());
+    }
+
+    let store = Store::with_backend(backend, true)?;
+    let (output, expires_at) = render_prompt(
+        store.tasks(),
+        tag.as_deref(),
+        project.as_deref(),
+        format.as_deref().unwrap_or(DEFAULT_PROMPT_FORMAT),
+        task_format.as_deref().unwrap_or(DEFAULT_PROMPT_TASK_FORMAT),
+        config,
+        now,
+    )?;
+    if let Some(key) = key {
+        if let Err(e) = prompt_cache::store(&data_path, &key, &output, expires_at) {
+            eprintln!("Warning: could not update prompt cache: {}", e);
+        }
+    }
+    if !output.is_empty() {
+        println!("{}", output);
+    }
+    Ok(())
+}
+
+// The prompt line for `all_tasks` (empty when there's nothing to show) and the
+// moment it next changes on its own because a due time is coming up
+fn render_prompt(
+    all_tasks: &[Task],
+    tag: Option<&str>,
+    project: Option<&str>,
+    format: &str,
+    task_format: &str,
+    config: &Config,
+    now: NaiveDateTime,
+) -> Result<(String, Option<NaiveDateTime>)> {
+    let today_date = now.date();
+    let today = today_date.format("%Y-%m-%d").to_string();
+
+    let (week_start, _) = get_week_range(today_date, config.week_start());
 
     // Pre-calculate the date comparison values to avoid repeated parsing
     let mut tasks_to_display: Vec<(String, &Task)> = all_tasks
         .iter()
+        .filter(|t| matches_tag_and_project(t, tag, project))
+        // Blocked tasks can't be worked on yet, so they don't count as pending
+        .filter(|t| t.done || t.open_blockers(all_tasks).is_empty())
         .filter_map(|t| {
             if let Ok(task_date) = parse_date_str(&t.date) {
                 // Condition 1: Undone tasks from the start of the week up to (but not including) today
@@ -353,74 +1315,215 @@ pub fn prompt_today() -> Result<()> {
         })
         .collect();
 
-    // Sort by date and then by ID
+    // Sort by date and due time, then by priority and ID
     tasks_to_display.sort_by(|a, b| {
-        a.0.cmp(&b.0).then_with(|| a.1.id.cmp(&b.1.id))
+        a.0.cmp(&b.0)
+            .then_with(|| a.1.schedule_key().cmp(&b.1.schedule_key()))
     });
 
+    if tasks_to_display.is_empty() {
+        return Ok((String::new(), None));
+    }
+
     let mut parts: Vec<String> = Vec::new();
-    for (_, t) in tasks_to_display {
-        let icon = if t.done {
-            "".green().to_string() // Nerd Font: NF-mdi-check
-        } else if t.reuse_by.is_some() {
-            "".yellow().to_string() // Nerd Font: NF-mdi-sync
-        } else {
-            "".red().to_string() // Nerd Font: NF-oct-tasklist
-        };
-        parts.push(format!("{:2}#{}", icon, t.id));
+    for (_, t) in &tasks_to_display {
+        let part = render_template(task_format, |key| {
+            Some(match key {
+                "icon" => prompt_icon(t, now, config),
+                "id" => t.id.to_string(),
+                "task" => t.task.clone(),
+                "date" => config.format_date(parse_date_str(&t.date).ok()?),
+                "time" => t.due_time.clone().unwrap_or_default(),
+                "priority" => t.priority.map_or("", |p| p.label()).to_string(),
+                _ => return None,
+            })
+        })?;
+        parts.push(part);
     }
 
-    if !parts.is_empty() {
-        println!("{}", parts.join(" "));
-    }
-    Ok(())
+    let pending: Vec<&Task> = tasks_to_display
+        .iter()
+        .map(|(_, t)| *t)
+        .filter(|t| !t.done)
+        .collect();
+    let count = |pred: &dyn Fn(&Task) -> bool| pending.iter().filter(|t| pred(t)).count();
+    let overdue = count(&|t| {
+        t.date.as_str() < today.as_str() || minutes_until_due(t, now).is_some_and(|m| m < 0)
+    });
+    let reused = count(&|t| t.reuse_by.is_some());
+    let high = count(&|t| t.priority == Some(Priority::High));
+    let next = pending.first();
+    let icon = |icon: &String, color: &String| {
+        icon.color(color.parse::<Color>().unwrap_or(Color::White))
+            .to_string()
+    };
+
+    let output = render_template(format, |key| {
+        Some(match key {
+            "tasks" => parts.join(" "),
+            "pending" => pending.len().to_string(),
+            "done" => (tasks_to_display.len() - pending.len()).to_string(),
+            "overdue" => overdue.to_string(),
+            "reused" => reused.to_string(),
+            "high" => high.to_string(),
+            "total" => tasks_to_display.len().to_string(),
+            "next" => next.map_or(String::new(), |t| {
+                truncate_string(&t.task, config.truncate_width)
+                    .trim_end()
+                    .to_string()
+            }),
+            "next_id" => next.map_or(String::new(), |t| t.id.to_string()),
+            "done_icon" => icon(&config.icons.done, &config.colors.done),
+            "pending_icon" => icon(&config.icons.pending, &config.colors.pending),
+            "high_icon" => icon(&config.icons.high, &config.colors.high),
+            "reused_icon" => icon(&config.icons.reused, &config.colors.reused),
+            "overdue_icon" => icon(&config.icons.overdue, &config.colors.overdue),
+            _ => return None,
+        })
+    })?;
+
+    // Minutes are truncated, so a task turns urgent 31 minutes before its due
+    // time and counts as late one minute after it
+    let expires_at = pending
+        .iter()
+        .filter_map(|t| {
+            let time = chrono::NaiveTime::parse_from_str(t.due_time.as_deref()?, "%H:%M").ok()?;
+            let due = safe_parse_date_str(&t.date)?.and_time(time);
+            [
+                due - chrono::Duration::minutes(URGENT_MINUTES + 1),
+                due + chrono::Duration::minutes(1),
+            ]
+            .into_iter()
+            .find(|&change| change > now)
+        })
+        .min();
+    Ok((output, expires_at))
 }
 
-pub fn review() -> Result<()> {
-    let all_tasks = match TaskStore::get_all_tasks() {
-        Ok(tasks) => tasks,
-        Err(e) => {
-            eprintln!("Error retrieving tasks: {}", e);
-            return Ok(());
-        }
+// The coloured status icon shown for a task in the prompt
+fn prompt_icon(t: &Task, now: NaiveDateTime, config: &Config) -> String {
+    // Tasks that are late or due within half an hour are flagged like high priority
+    let urgent = minutes_until_due(t, now).is_some_and(|m| m <= URGENT_MINUTES);
+    let (icon, color) = if t.done {
+        (&config.icons.done, &config.colors.done)
+    } else if t.priority == Some(Priority::High) || urgent {
+        (&config.icons.high, &config.colors.high)
+    } else if t.reuse_by.is_some() {
+        (&config.icons.reused, &config.colors.reused)
+    } else {
+        (&config.icons.pending, &config.colors.pending)
     };
-    let today = today_str();
-    let today_date = match parse_date_str(&today) {
-        Ok(date) => date,
-        Err(_) => {
-            eprintln!("Error: Failed to parse today's date");
-            return Ok(());
+    let mut icon = icon.color(color.parse::<Color>().unwrap_or(Color::White));
+    if t.priority == Some(Priority::High) && !t.done {
+        icon = icon.bold();
+    }
+    icon.to_string()
+}
+
+// Fill `{name}` placeholders from `lookup`; `{{` and `}}` are literal braces
+fn render_template(template: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut key = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => key.push(c),
+                        None => {
+                            return Err(TdError::InvalidInput(format!(
+                                "Unclosed '{{' in format '{}'.",
+                                template
+                            )))
+                        }
+                    }
+                }
+                let value = lookup(key.trim()).ok_or_else(|| {
+                    TdError::InvalidInput(format!("Unknown placeholder '{{{}}}' in format.", key))
+                })?;
+                out.push_str(&value);
+            }
+            '}' => {
+                return Err(TdError::InvalidInput(format!(
+                    "Unmatched '}}' in format '{}'.",
+                    template
+                )))
+            }
+            c => out.push(c),
         }
+    }
+    Ok(out)
+}
+
+pub fn review(
+    store: &Store,
+    tag: Option<String>,
+    project: Option<String>,
+    config: &Config,
+) -> Result<()> {
+    let all_tasks = store.tasks();
+    let today_date = parse_date_str(&today_str())?;
+    // Either a fixed number of days from the config, or the start of the week
+    let (cutoff, heading, empty_message) = match config.review_days {
+        Some(days) => (
+            chrono::Duration::try_days(days)
+                .and_then(|d| today_date.checked_sub_signed(d))
+                .ok_or_else(|| {
+                    TdError::InvalidDate(format!("review_days {} is out of range.", days))
+                })?,
+            format!("Tasks Overdue By More Than {} Days", days),
+            format!("No tasks currently overdue by more than {} days.", days),
+        ),
+        None => (
+            get_week_range(today_date, config.week_start()).0,
+            "Tasks Overdue From Before Current Week".to_string(),
+            "No tasks currently overdue from before the current week.".to_string(),
+        ),
     };
-    let (week_start, _) = get_current_week_range(today_date);
 
-    println!("\n--- Tasks Overdue From Before Current Week ---");
+    println!("\n--- {} ---", heading);
 
     let mut old_overdue_tasks: Vec<&Task> = all_tasks
         .iter()
+        .filter(|t| matches_tag_and_project(t, tag.as_deref(), project.as_deref()))
         .filter(|t| {
             if let Ok(task_date) = parse_date_str(&t.date) {
-                !t.done && task_date < week_start
+                !t.done && task_date < cutoff
             } else {
                 false // Exclude tasks with invalid dates
             }
         })
         .collect();
 
-    old_overdue_tasks.sort_by_key(|t| &t.date);
+    old_overdue_tasks.sort_by_key(|t| (&t.date, t.priority_rank()));
 
     if old_overdue_tasks.is_empty() {
-        println!("No tasks currently overdue from before the current week.");
+        println!("{}", empty_message);
     } else {
-        println!("ID   Date         Days Overdue   Task Description");
-        println!("---  -----------  ------------   -----------------------");
+        println!("ID   Pri  Date         Days Overdue   Task Description");
+        println!("---  ---  -----------  ------------   -----------------------");
         for t in old_overdue_tasks {
             if let Ok(task_date) = parse_date_str(&t.date) {
                 let days_overdue = (today_date - task_date).num_days();
-                let task_display = truncate_string(&t.task, 22);
+                let task_display = truncate_string(&t.task, config.truncate_width);
+                let priority = t.priority.map_or("-", |p| p.label());
                 println!(
-                    "{:<4} {:<11}  {:<12}   {}:",
-                    t.id, t.date, days_overdue, task_display
+                    "{:<4} {:<3}  {:<11}  {:<12}   {}:",
+                    t.id,
+                    priority,
+                    config.format_date(task_date),
+                    days_overdue,
+                    task_display
                 );
             }
         }
@@ -433,51 +1536,356 @@ pub fn review() -> Result<()> {
     Ok(())
 }
 
-pub fn reuse(id: usize, date: Option<String>) -> Result<()> {
-    match TaskStore::find_task_by_id(id) {
-        Ok(Some(task_to_reuse)) => {
-            let new_id = TaskStore::get_max_id()? + 1;
+pub fn reuse(store: &mut Store, id: usize, date: Option<String>, config: &Config) -> Result<()> {
+    let task_to_reuse = store.find(id).cloned().ok_or(TdError::NotFound(id))?;
+    let new_id = next_task_id(store)?;
 
-            // If task_to_reuse was itself a reused task, we point to its original.
-            // Otherwise, we point to it.
-            let new_reuse_by_id = task_to_reuse.reuse_by.unwrap_or(id);
+    // If task_to_reuse was itself a reused task, we point to its original.
+    // Otherwise, we point to it.
+    let new_reuse_by_id = task_to_reuse.reuse_by.unwrap_or(id);
 
-            let date = match date {
-                Some(d) => {
-                    // Validate date format before reusing
-                    if parse_date_str(&d).is_err() {
-                        eprintln!("Error: Invalid date format '{}'. Please use YYYY-MM-DD.", d);
-                        return Ok(()); // Return Ok to prevent program exit
-                    }
-                    d
+    let date = match date {
+        // Resolve relative dates before reusing
+        Some(d) => resolve_date_str(&d, config.week_start())?,
+        None => today_str(),
+    };
+
+    let new_task = Task {
+        id: new_id,
+        task: task_to_reuse.task.clone(), // Clone task description
+        date,
+        done: false,
+        reuse_by: Some(new_reuse_by_id),
+        tags: task_to_reuse.tags.clone(),
+        project: task_to_reuse.project.clone(),
+        priority: task_to_reuse.priority,
+        recurrence: task_to_reuse.recurrence.clone(),
+        created_at: Some(now_str()),
+        completed_at: None,
+        notes: task_to_reuse.notes.clone(),
+        parent: task_to_reuse.parent,
+        blocked_by: task_to_reuse.blocked_by.clone(),
+        due_time: task_to_reuse.due_time.clone(),
+        duration: task_to_reuse.duration,
+    };
+
+    store.add(new_task);
+
+    // Mark the original task as done
+    let mut original_task = task_to_reuse;
+    if !original_task.done {
+        original_task.done = true;
+        original_task.completed_at = Some(now_str());
+    }
+    store.update(id, original_task);
+
+    println!(
+        "[+] Reused task #{} as new task #{}. Original task marked done.",
+        id, new_id
+    );
+    Ok(())
+}
+
+pub fn reuse_many(
+    store: &mut Store,
+    ids: &[usize],
+    date: Option<String>,
+    config: &Config,
+) -> Result<()> {
+    ensure_tasks_exist(store, ids)?;
+    for &id in ids {
+        reuse(store, id, date.clone(), config)?;
+    }
+    Ok(())
+}
+
+// How a saved command goes into the undo journal
+pub enum History {
+    // Any other command, recorded as typed
+    Record(String),
+    // Undo and redo move the entry they applied to the other stack
+    Undo,
+    Redo,
+}
+
+// Update the undo journal once a command's `changes` have been saved, so a
+// failed write can't leave the journal ahead of the tasks
+pub fn record_history(store: &Store, history: History, changes: Vec<Change>) -> Result<()> {
+    let mut journal = load_journal(store)?;
+    match history {
+        History::Record(_) if changes.is_empty() => return Ok(()),
+        History::Record(command) => journal.push(JournalEntry {
+            command,
+            timestamp: chrono::Local::now().to_rfc3339(),
+            changes,
+        }),
+        History::Undo => match journal.undo.pop() {
+            Some(entry) => journal.redo.push(entry),
+            None => return Ok(()),
+        },
+        History::Redo => match journal.redo.pop() {
+            Some(entry) => journal.undo.push(entry),
+            None => return Ok(()),
+        },
+    }
+    save_journal(store, &journal)
+}
+
+// Restore the tasks from before the last recorded command; the journal is
+// updated by `record_history` after the store is saved
+pub fn undo(store: &mut Store) -> Result<()> {
+    let journal = load_journal(store)?;
+    let Some(entry) = journal.undo.last() else {
+        println!("Nothing to undo.");
+        return Ok(());
+    };
+
+    let restored = restore_task_states(
+        store,
+        entry.changes.iter().map(|c| (c.id, c.before.clone())),
+    )?;
+    println!(
+        "[↶] Undid `td {}` ({} task(s) restored).",
+        entry.command, restored
+    );
+    Ok(())
+}
+
+pub fn redo(store: &mut Store) -> Result<()> {
+    let journal = load_journal(store)?;
+    let Some(entry) = journal.redo.last() else {
+        println!("Nothing to redo.");
+        return Ok(());
+    };
+
+    let changed =
+        restore_task_states(store, entry.changes.iter().map(|c| (c.id, c.after.clone())))?;
+    println!(
+        "[↷] Redid `td {}` ({} task(s) changed).",
+        entry.command, changed
+    );
+    Ok(())
+}
+
+// Put tasks back into a recorded state; `None` means the task must not exist.
+// Tasks that were archived since stay in the archive, so an ID is never in
+// both places. Returns how many tasks were restored.
+fn restore_task_states(
+    store: &mut Store,
+    states: impl Iterator<Item = (usize, Option<Task>)>,
+) -> Result<usize> {
+    let states: Vec<(usize, Option<Task>)> = states.collect();
+    // Checked up front so a failed archive read changes nothing
+    let mut archived = Vec::new();
+    for (id, state) in &states {
+        if state.is_some()
+            && store.find(*id).is_none()
+            && archive::find_archived_task(store, *id)?.is_some()
+        {
+            archived.push(*id);
+        }
+    }
+    if !archived.is_empty() {
+        eprintln!(
+            "Warning: Task(s) {} have been archived since and were left in the archive.",
+            format_id_list(&archived)
+        );
+    }
+
+    let mut restored = 0;
+    for (id, state) in states {
+        match state {
+            Some(_) if archived.contains(&id) => continue,
+            Some(task) => {
+                if !store.update(id, task.clone()) {
+                    store.add(task);
                 }
-                None => today_str(),
-            };
+            }
+            None => {
+                store.remove(id);
+            }
+        }
+        restored += 1;
+    }
+    Ok(restored)
+}
 
-            let new_task = Task {
-                id: new_id,
-                task: task_to_reuse.task.clone(), // Clone task description
-                date,
-                done: false,
-                reuse_by: Some(new_reuse_by_id),
-            };
+// Parse task IDs given as "3", "10-20" or "3,5,7" into a sorted, de-duplicated list
+pub fn parse_id_args(args: &[String]) -> Result<Vec<usize>> {
+    let mut ids = Vec::new();
+    for arg in args {
+        for part in arg.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let invalid =
+                || TdError::InvalidInput(format!("Invalid task ID '{}'. Use N, N-M or N,M.", part));
+            if let Some((start, end)) = part.split_once('-') {
+                let start: usize = start.trim().parse().map_err(|_| invalid())?;
+                let end: usize = end.trim().parse().map_err(|_| invalid())?;
+                // Guard against typos like 1-99999999 expanding into millions of IDs
+                if start > end || end - start > 100_000 {
+                    return Err(invalid());
+                }
+                ids.extend(start..=end);
+            } else {
+                ids.push(part.parse().map_err(|_| invalid())?);
+            }
+        }
+    }
+    ids.sort_unstable();
+    ids.dedup();
+    Ok(ids)
+}
+
+// Whether `from` waits on `target`, directly or through other tasks
+fn depends_on(tasks: &[Task], from: usize, target: usize) -> bool {
+    let mut seen = std::collections::HashSet::new();
+    let mut pending = vec![from];
+    while let Some(id) = pending.pop() {
+        if id == target {
+            return true;
+        }
+        if seen.insert(id) {
+            if let Some(t) = tasks.iter().find(|t| t.id == id) {
+                pending.extend(&t.blocked_by);
+            }
+        }
+    }
+    false
+}
 
-            TaskStore::add_task(new_task)?;
+// "#3, #5"
+fn format_id_list(ids: &[usize]) -> String {
+    ids.iter()
+        .map(|id| format!("#{}", id))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
-            // Mark the original task as done
-            let mut original_task = task_to_reuse;
-            original_task.done = true;
-            TaskStore::update_task(id, original_task)?;
+// Number of ancestors above a task; parent cycles stop the walk
+fn task_depth(tasks: &[Task], id: usize) -> usize {
+    let mut seen = std::collections::HashSet::from([id]);
+    let mut current = tasks.iter().find(|t| t.id == id).and_then(|t| t.parent);
+    while let Some(parent_id) = current {
+        if !seen.insert(parent_id) {
+            break;
+        }
+        current = tasks
+            .iter()
+            .find(|t| t.id == parent_id)
+            .and_then(|t| t.parent);
+    }
+    seen.len() - 1
+}
 
-            println!(
-                "[+] Reused task #{} as new task #{}. Original task marked done.",
-                id, new_id
-            );
+// Open `path` in the user's editor and wait for it to exit
+fn run_editor(path: &std::path::Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+
+    // Go through the shell so editors configured with arguments ("code --wait") work
+    #[cfg(unix)]
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(&editor)
+        .arg(path)
+        .status();
+    #[cfg(not(unix))]
+    let status = std::process::Command::new(&editor).arg(path).status();
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(TdError::Other(anyhow::anyhow!(
+            "Editor '{}' exited with {}",
+            editor,
+            status
+        ))),
+        Err(e) => Err(TdError::Other(anyhow::anyhow!(
+            "Could not run editor '{}': {}",
+            editor,
+            e
+        ))),
+    }
+}
+
+pub(crate) fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// Clone a completed recurring task onto its next date, keeping the reuse_by
+// lineage. Returns `None` if that date is too far out to represent.
+fn spawn_next_occurrence(
+    store: &mut Store,
+    task: &Task,
+    rule: &Recurrence,
+) -> Result<Option<(usize, String)>> {
+    let today_date = parse_date_str(&today_str())?;
+    let Some(mut next_date) =
+        rule.next_after(safe_parse_date_str(&task.date).unwrap_or(today_date))
+    else {
+        return Ok(None);
+    };
+    // Skip occurrences that are already in the past instead of piling them up
+    while next_date < today_date {
+        match rule.next_after(next_date) {
+            Some(date) => next_date = date,
+            None => return Ok(None),
         }
-        Ok(None) => eprintln!("Task #{} not found.", id),
-        Err(e) => eprintln!("Error retrieving task #{}: {}", id, e),
     }
-    Ok(())
+
+    let new_id = next_task_id(store)?;
+    let next_date = next_date.format("%Y-%m-%d").to_string();
+    store.add(Task {
+        id: new_id,
+        task: task.task.clone(),
+        date: next_date.clone(),
+        done: false,
+        reuse_by: Some(task.reuse_by.unwrap_or(task.id)),
+        tags: task.tags.clone(),
+        project: task.project.clone(),
+        priority: task.priority,
+        recurrence: task.recurrence.clone(),
+        created_at: Some(now_str()),
+        completed_at: None,
+        notes: task.notes.clone(),
+        parent: task.parent,
+        blocked_by: task.blocked_by.clone(),
+        due_time: task.due_time.clone(),
+        duration: task.duration,
+    });
+    Ok(Some((new_id, next_date)))
+}
+
+// `td log --since`: a lookback like 7d / 2w / 1m, or any date `--date` accepts
+pub(crate) fn parse_since(
+    since: &str,
+    today_date: NaiveDate,
+    week_start: chrono::Weekday,
+) -> Result<NaiveDate> {
+    let trimmed = since.trim();
+    let is_lookback = trimmed.starts_with(|c: char| c.is_ascii_digit())
+        && trimmed.ends_with(['d', 'w', 'm', 'y']);
+    if is_lookback {
+        parse_natural_date(&format!("-{}", trimmed), today_date, week_start)
+    } else {
+        parse_natural_date(trimmed, today_date, week_start)
+    }
+}
+
+pub(crate) fn parse_timestamp(s: &str) -> Option<chrono::DateTime<chrono::Local>> {
+    chrono::DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|dt| dt.with_timezone(&chrono::Local))
+}
+
+fn format_timestamp(s: &str) -> Option<String> {
+    parse_timestamp(s).map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
 }
 
 // Helper functions that were originally in the file
@@ -485,21 +1893,33 @@ fn parse_date_str(date_str: &str) -> std::result::Result<chrono::NaiveDate, chro
     chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
 }
 
+// Resolve a user-supplied date (ISO or relative, e.g. "tomorrow", "+3d") to YYYY-MM-DD
+fn resolve_date_str(date_str: &str, week_start: chrono::Weekday) -> Result<String> {
+    let today_date = parse_date_str(&today_str())?;
+    let date = parse_natural_date(date_str, today_date, week_start)?;
+    Ok(date.format("%Y-%m-%d").to_string())
+}
+
 // Safe wrapper for date parsing that handles errors gracefully
 fn safe_parse_date_str(date_str: &str) -> Option<chrono::NaiveDate> {
     chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d").ok()
 }
 
-fn get_current_week_range(today: chrono::NaiveDate) -> (chrono::NaiveDate, chrono::NaiveDate) {
-    let weekday = today.weekday();
-    let monday = today - chrono::Duration::days(weekday.num_days_from_monday() as i64);
-    let sunday = monday + chrono::Duration::days(6);
-    (monday, sunday)
+// Seven-day week starting on `week_start` that contains `anchor`
+fn get_week_range(
+    anchor: chrono::NaiveDate,
+    week_start: chrono::Weekday,
+) -> (chrono::NaiveDate, chrono::NaiveDate) {
+    let offset = anchor.weekday().days_since(week_start);
+    let first = anchor - chrono::Duration::days(offset as i64);
+    let last = first + chrono::Duration::days(6);
+    (first, last)
 }
 
-fn get_current_month_range(today: chrono::NaiveDate) -> (chrono::NaiveDate, chrono::NaiveDate) {
-    let year = today.year();
-    let month = today.month();
+// First and last day of the month containing `anchor`
+fn get_month_range(anchor: chrono::NaiveDate) -> (chrono::NaiveDate, chrono::NaiveDate) {
+    let year = anchor.year();
+    let month = anchor.month();
     let month_start = chrono::NaiveDate::from_ymd_opt(year, month, 1)
         .expect("Invalid date: failed to create first day of month");
     let month_end = if month == 12 {
@@ -514,6 +1934,90 @@ fn get_current_month_range(today: chrono::NaiveDate) -> (chrono::NaiveDate, chro
     (month_start, month_end)
 }
 
+// Split "+tag" and "@project" tokens out of the task text
+fn parse_task_input(input: &str) -> (String, Vec<String>, Option<String>) {
+    let mut words = Vec::new();
+    let mut tags: Vec<String> = Vec::new();
+    let mut project = None;
+
+    for word in input.split_whitespace() {
+        if let Some(tag) = word.strip_prefix('+').filter(|t| !t.is_empty()) {
+            if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.to_string());
+            }
+        } else if let Some(p) = word.strip_prefix('@').filter(|p| !p.is_empty()) {
+            project = Some(p.to_string());
+        } else {
+            words.push(word);
+        }
+    }
+
+    (words.join(" "), tags, project)
+}
+
+fn matches_tag_and_project(t: &Task, tag: Option<&str>, project: Option<&str>) -> bool {
+    tag.is_none_or(|tag| t.has_tag(tag)) && project.is_none_or(|p| t.in_project(p))
+}
+
+// Task description with its priority marker, tags and project
+fn format_task_label(t: &Task) -> String {
+    let mut label = match t.priority {
+        Some(Priority::High) => format!("{} {}", "(H)".red().bold(), t.task),
+        Some(Priority::Medium) => format!("{} {}", "(M)".yellow(), t.task),
+        Some(Priority::Low) => format!("{} {}", "(L)".blue(), t.task),
+        None => t.task.clone(),
+    };
+    for tag in &t.tags {
+        label.push_str(&format!(" {}", format!("+{}", tag).cyan()));
+    }
+    if let Some(ref project) = t.project {
+        label.push_str(&format!(" {}", format!("@{}", project).magenta()));
+    }
+    label
+}
+
+// First and last day of the week `week` names: "this" or any date inside the
+// wanted week use the configured week start, while an ISO week such as
+// 2025-W31 always runs Monday to Sunday
+fn parse_week_arg(
+    week: &str,
+    today_date: NaiveDate,
+    week_start: chrono::Weekday,
+) -> Result<(NaiveDate, NaiveDate)> {
+    let invalid = || TdError::InvalidDate(format!("Invalid week '{}'. Use YYYY-Www.", week));
+    if week.is_empty() || week.eq_ignore_ascii_case("this") {
+        return Ok(get_week_range(today_date, week_start));
+    }
+    if let Some((year, num)) = week.to_uppercase().split_once("-W") {
+        return year
+            .parse()
+            .ok()
+            .zip(num.parse().ok())
+            .and_then(|(year, num)| NaiveDate::from_isoywd_opt(year, num, chrono::Weekday::Mon))
+            .map(|monday| get_week_range(monday, chrono::Weekday::Mon))
+            .ok_or_else(invalid);
+    }
+    parse_natural_date(week, today_date, week_start)
+        .map(|date| get_week_range(date, week_start))
+        .map_err(|_| invalid())
+}
+
+// "this", a month such as 2025-07, or any date inside the wanted month
+fn parse_month_arg(
+    month: &str,
+    today_date: NaiveDate,
+    week_start: chrono::Weekday,
+) -> Result<NaiveDate> {
+    if month.is_empty() || month.eq_ignore_ascii_case("this") {
+        return Ok(today_date);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d") {
+        return Ok(date);
+    }
+    parse_natural_date(month, today_date, week_start)
+        .map_err(|_| TdError::InvalidDate(format!("Invalid month '{}'. Use YYYY-MM.", month)))
+}
+
 fn truncate_string(s: &str, max_chars: usize) -> String {
     if s.chars().count() > max_chars {
         let mut truncated = s.chars().take(max_chars).collect::<String>();
@@ -526,42 +2030,13 @@ fn truncate_string(s: &str, max_chars: usize) -> String {
     }
 }
 
-fn list_by_week(tasks: &[Task], today_date: NaiveDate) {
-    let (week_start, week_end) = get_current_week_range(today_date);
-    let tasks_to_display: Vec<&Task> = tasks
-        .iter()
-        .filter(|t| {
-            if let Ok(task_date) = parse_date_str(&t.date) {
-                task_date >= week_start && task_date <= week_end
-            } else {
-                false // Exclude tasks with invalid dates
-            }
-        })
-        .collect();
-    println!(
-        "--- For Current Week ({} to {}) ---",
-        week_start.format("%Y-%m-%d"),
-        week_end.format("%Y-%m-%d")
-    );
-    for t in tasks_to_display {
-        let status = if t.done {
-            "[✓]".green().to_string()
-        } else {
-            "[ ]".red().to_string()
-        };
-        let extra_info = get_task_extra_info(t, today_date);
-        // Format the output with better alignment - include extra info on same line
-        if !extra_info.is_empty() {
-            println!("{:>4} {} {} {}", t.id, status, t.task, extra_info);
-        } else {
-            println!("{:>4} {} {}", t.id, status, t.task);
-        }
-    }
-}
-
-fn list_default(tasks: &[Task], today_date: NaiveDate) {
-    let (week_start, _) = get_current_week_range(today_date);
-
+fn list_default(
+    store: &Store,
+    tasks: &[Task],
+    today_date: NaiveDate,
+    week_start: NaiveDate,
+    show_times: bool,
+) {
     let mut tasks_to_display: Vec<&Task> = tasks
         .iter()
         .filter(|t| {
@@ -574,120 +2049,659 @@ fn list_default(tasks: &[Task], today_date: NaiveDate) {
             }
         })
         .collect();
-    tasks_to_display.sort_by_key(|t| (&t.date, t.id));
+    tasks_to_display.sort_by_key(|t| t.schedule_key());
 
     println!("--- Current Tasks ---");
 
     if tasks_to_display.is_empty() {
         println!("No tasks for today or overdue this week.");
     } else {
-        for t in tasks_to_display {
-            let status = if t.done {
-                "[✓]".green().to_string()
-            } else {
-                "[ ]".red().to_string()
-            };
-            let extra_info = get_task_extra_info(t, today_date);
-            // Format the output with better alignment - include extra info on same line
-            if !extra_info.is_empty() {
-                println!("{:>4} {} {} {}", t.id, status, t.task, extra_info);
-            } else {
-                println!("{:>4} {} {}", t.id, status, t.task);
-            }
-        }
+        print_task_tree(store, &tasks_to_display, today_date, show_times);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::task_store::TaskStore;
+    use tempfile::TempDir;
+
+    // An empty store of its own for each test
+    fn test_store() -> (TempDir, Store) {
+        let temp_dir = TempDir::new().unwrap();
+        let store = Store::open(temp_dir.path().join("todo.json")).unwrap();
+        (temp_dir, store)
+    }
 
     #[test]
     fn test_add_task() {
-        // Reset the store for this test
-        TaskStore::reset_store_for_testing();
+        let (_dir, mut store) = test_store();
 
         // Add a task
-        let result = add("Test task".to_string(), Some("2023-01-01".to_string()));
+        let result = add(
+            &mut store,
+            "Test task".to_string(),
+            AddOptions {
+                date: Some("2023-01-01".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        );
         assert!(result.is_ok());
 
         // Check that the task was added
-        let all_tasks = TaskStore::get_all_tasks().unwrap();
+        let all_tasks = store.tasks().to_vec();
         assert_eq!(all_tasks.len(), 1);
         assert_eq!(all_tasks[0].task, "Test task");
         assert_eq!(all_tasks[0].date, "2023-01-01");
-        assert_eq!(all_tasks[0].done, false);
+        assert!(!all_tasks[0].done);
     }
 
     #[test]
     fn test_mark_done() {
-        // Reset the store for this test
-        TaskStore::reset_store_for_testing();
+        let (_dir, mut store) = test_store();
 
         // Add a task first
-        let result = add("Test task".to_string(), Some("2023-01-01".to_string()));
+        let result = add(
+            &mut store,
+            "Test task".to_string(),
+            AddOptions {
+                date: Some("2023-01-01".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        );
         assert!(result.is_ok());
 
         // Verify the task is not done initially
-        let all_tasks = TaskStore::get_all_tasks().unwrap();
+        let all_tasks = store.tasks().to_vec();
         assert_eq!(all_tasks.len(), 1);
-        assert_eq!(all_tasks[0].done, false);
+        assert!(!all_tasks[0].done);
+        assert!(all_tasks[0].created_at.is_some());
+        assert!(all_tasks[0].completed_at.is_none());
 
         // Mark the task as done
-        let result = mark_done(1);
+        let result = mark_done(&mut store, 1, false);
         assert!(result.is_ok());
 
         // Verify the task is now done
-        let all_tasks = TaskStore::get_all_tasks().unwrap();
-        assert_eq!(all_tasks[0].done, true);
+        let all_tasks = store.tasks().to_vec();
+        assert!(all_tasks[0].done);
+        let completed_at = all_tasks[0].completed_at.clone();
+        assert!(completed_at.as_deref().and_then(parse_timestamp).is_some());
+
+        // Marking it done again keeps the original completion time
+        mark_done(&mut store, 1, false).unwrap();
+        let all_tasks = store.tasks().to_vec();
+        assert_eq!(all_tasks[0].completed_at, completed_at);
+    }
+
+    #[test]
+    fn test_subtasks_block_or_cascade_done() {
+        let (_dir, mut store) = test_store();
+
+        add(
+            &mut store,
+            "Ship v2".to_string(),
+            AddOptions::default(),
+            &Config::default(),
+        )
+        .unwrap();
+        add(
+            &mut store,
+            "Write tests".to_string(),
+            AddOptions {
+                parent: Some("1".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        )
+        .unwrap();
+        add(
+            &mut store,
+            "Unit tests".to_string(),
+            AddOptions {
+                parent: Some("2".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        )
+        .unwrap();
+        // Unknown parents are rejected
+        let result = add(
+            &mut store,
+            "Orphan".to_string(),
+            AddOptions {
+                parent: Some("42".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        );
+        assert!(matches!(result, Err(TdError::NotFound(42))));
+
+        let tasks = store.tasks().to_vec();
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[2].parent, Some(2));
+        assert_eq!(task_depth(&tasks, 3), 2);
+
+        // The parent stays open while a subtask is open
+        let result = mark_done(&mut store, 1, false);
+        assert!(matches!(result, Err(TdError::InvalidInput(_))));
+        assert!(!store.find(1).unwrap().clone().done);
+
+        // Cascading completes the whole subtree
+        mark_done(&mut store, 1, true).unwrap();
+        let tasks = store.tasks().to_vec();
+        assert!(tasks.iter().all(|t| t.done));
+    }
+
+    #[test]
+    fn test_cascade_done_with_parent_cycle() {
+        let (dir, store) = test_store();
+        drop(store);
+        let task = |id: usize, parent: usize| Task {
+            id,
+            task: format!("Task {}", id),
+            date: "2025-08-01".to_string(),
+            parent: Some(parent),
+            ..Default::default()
+        };
+        let path = dir.path().join("todo.json");
+        crate::model::save_tasks(&path, &[task(1, 2), task(2, 1)]).unwrap();
+
+        // The cycle is broken on load, so cascading ends
+        let mut store = Store::open(&path).unwrap();
+        mark_done(&mut store, 1, true).unwrap();
+        assert!(store.tasks().iter().all(|t| t.done));
+    }
+
+    #[test]
+    fn test_render_template() {
+        let lookup = |key: &str| match key {
+            "pending" => Some("3".to_string()),
+            "total" => Some("5".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            render_template("{pending}/{total} left", lookup).unwrap(),
+            "3/5 left"
+        );
+        assert_eq!(render_template("{{{ pending }}}", lookup).unwrap(), "{3}");
+        assert!(render_template("{unknown}", lookup).is_err());
+        assert!(render_template("{pending", lookup).is_err());
+        assert!(render_template("pending}", lookup).is_err());
+    }
+
+    #[test]
+    fn test_render_prompt_expiry() {
+        let now = NaiveDate::parse_from_str("2025-08-06", "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(14, 0, 0)
+            .unwrap();
+        let task = |id: usize, due_time: Option<&str>| Task {
+            id,
+            task: format!("Task {}", id),
+            date: "2025-08-06".to_string(),
+            due_time: due_time.map(str::to_string),
+            ..Default::default()
+        };
+        let render = |tasks: &[Task]| {
+            render_prompt(
+                tasks,
+                None,
+                None,
+                "{pending}",
+                "{id}",
+                &Config::default(),
+                now,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(render(&[]), (String::new(), None));
+        assert_eq!(render(&[task(1, None)]), ("1".to_string(), None));
+
+        // Cached output expires when a task turns urgent or late
+        let at = |h, m| now.date().and_hms_opt(h, m, 0).unwrap();
+        let (_, expires) = render(&[task(1, Some("16:00")), task(2, Some("14:20"))]);
+        assert_eq!(expires, Some(at(14, 21)));
+        let (_, expires) = render(&[task(1, Some("16:00"))]);
+        assert_eq!(expires, Some(at(15, 29)));
+        let (_, expires) = render(&[task(1, Some("12:00"))]);
+        assert_eq!(expires, None);
+    }
+
+    #[test]
+    fn test_due_status() {
+        let now = NaiveDate::parse_from_str("2025-08-06", "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(14, 5, 0)
+            .unwrap();
+        let task = |due_time: &str, duration: Option<u32>| Task {
+            id: 1,
+            task: "Meeting prep".to_string(),
+            date: "2025-08-06".to_string(),
+            due_time: Some(due_time.to_string()),
+            duration,
+            ..Default::default()
+        };
+
+        assert_eq!(due_status(&task("14:30", None), now).unwrap(), "due in 25m");
+        assert_eq!(due_status(&task("13:05", None), now).unwrap(), "late by 1h");
+        assert_eq!(
+            due_status(&task("14:00", Some(30)), now).unwrap(),
+            "in progress"
+        );
+        assert_eq!(
+            format_time_slot(&task("14:00", Some(90))).unwrap(),
+            "14:00-15:30"
+        );
+
+        // Untimed, finished and other-day tasks have no due status
+        let mut done = task("14:30", None);
+        done.done = true;
+        assert!(due_status(&done, now).is_none());
+        let mut tomorrow = task("09:00", None);
+        tomorrow.date = "2025-08-07".to_string();
+        assert!(due_status(&tomorrow, now).is_none());
+        let untimed = Task {
+            due_time: None,
+            ..task("14:30", None)
+        };
+        assert!(due_status(&untimed, now).is_none());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let today = NaiveDate::parse_from_str("2025-08-06", "%Y-%m-%d").unwrap();
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        let parse_since = |s: &str, today| parse_since(s, today, chrono::Weekday::Mon);
+
+        assert_eq!(parse_since("7d", today).unwrap(), date("2025-07-30"));
+        assert_eq!(parse_since("2w", today).unwrap(), date("2025-07-23"));
+        assert_eq!(parse_since("1m", today).unwrap(), date("2025-07-06"));
+        assert_eq!(
+            parse_since("2025-08-01", today).unwrap(),
+            date("2025-08-01")
+        );
+        assert_eq!(parse_since("yesterday", today).unwrap(), date("2025-08-05"));
+        assert!(parse_since("a while", today).is_err());
     }
 
     #[test]
     fn test_remove_task() {
-        // Reset the store for this test
-        TaskStore::reset_store_for_testing();
+        let (_dir, mut store) = test_store();
 
         // Add a task first
-        let result = add("Test task".to_string(), Some("2023-01-01".to_string()));
+        let result = add(
+            &mut store,
+            "Test task".to_string(),
+            AddOptions {
+                date: Some("2023-01-01".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        );
         assert!(result.is_ok());
 
         // Verify the task exists
-        let all_tasks = TaskStore::get_all_tasks().unwrap();
+        let all_tasks = store.tasks().to_vec();
         assert_eq!(all_tasks.len(), 1);
 
         // Remove the task
-        let result = remove(1);
+        let result = remove(&mut store, 1);
         assert!(result.is_ok());
 
         // Verify the task is gone
-        let all_tasks = TaskStore::get_all_tasks().unwrap();
+        let all_tasks = store.tasks().to_vec();
         assert_eq!(all_tasks.len(), 0);
     }
 
     #[test]
     fn test_edit_task() {
-        // Reset the store for this test
-        TaskStore::reset_store_for_testing();
+        let (_dir, mut store) = test_store();
 
         // Add a task first
-        let result = add("Test task".to_string(), Some("2023-01-01".to_string()));
+        let result = add(
+            &mut store,
+            "Test task".to_string(),
+            AddOptions {
+                date: Some("2023-01-01".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        );
         assert!(result.is_ok());
 
         // Verify the initial task
-        let all_tasks = TaskStore::get_all_tasks().unwrap();
+        let all_tasks = store.tasks().to_vec();
         assert_eq!(all_tasks.len(), 1);
         assert_eq!(all_tasks[0].task, "Test task");
 
         // Edit the task
-        let result = edit(1, Some("Updated task".to_string()), None);
+        let result = edit(
+            &mut store,
+            1,
+            EditOptions {
+                task: Some("Updated task".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        );
         assert!(result.is_ok());
 
         // Verify the task was updated
-        let all_tasks = TaskStore::get_all_tasks().unwrap();
+        let all_tasks = store.tasks().to_vec();
         assert_eq!(all_tasks[0].task, "Updated task");
     }
 
+    #[test]
+    fn test_add_parses_tags_and_project() {
+        let (_dir, mut store) = test_store();
+
+        let result = add(
+            &mut store,
+            "Fix CI +infra @backend".to_string(),
+            AddOptions {
+                tags: vec!["urgent".to_string(), "INFRA".to_string()],
+                ..Default::default()
+            },
+            &Config::default(),
+        );
+        assert!(result.is_ok());
+
+        let all_tasks = store.tasks().to_vec();
+        assert_eq!(all_tasks[0].task, "Fix CI");
+        assert_eq!(all_tasks[0].tags, vec!["infra", "urgent"]);
+        assert_eq!(all_tasks[0].project.as_deref(), Some("backend"));
+
+        // An explicit --project overrides the inline one
+        add(
+            &mut store,
+            "Deploy @backend".to_string(),
+            AddOptions {
+                project: Some("ops".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        )
+        .unwrap();
+        let task = store.find(2).unwrap().clone();
+        assert_eq!(task.project.as_deref(), Some("ops"));
+        assert!(matches_tag_and_project(&task, None, Some("OPS")));
+        assert!(!matches_tag_and_project(&task, Some("infra"), None));
+    }
+
+    #[test]
+    fn test_priority_add_and_edit() {
+        let (_dir, mut store) = test_store();
+
+        add(
+            &mut store,
+            "Ship release".to_string(),
+            AddOptions {
+                priority: Some("h".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        )
+        .unwrap();
+        let task = store.find(1).unwrap().clone();
+        assert_eq!(task.priority, Some(Priority::High));
+        assert_eq!(task.priority_rank(), 0);
+
+        edit(
+            &mut store,
+            1,
+            EditOptions {
+                priority: Some("low".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        )
+        .unwrap();
+        let task = store.find(1).unwrap().clone();
+        assert_eq!(task.priority, Some(Priority::Low));
+
+        edit(
+            &mut store,
+            1,
+            EditOptions {
+                priority: Some("none".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        )
+        .unwrap();
+        let task = store.find(1).unwrap().clone();
+        assert_eq!(task.priority, None);
+
+        // Invalid priorities are rejected without adding a task
+        let result = add(
+            &mut store,
+            "Bad".to_string(),
+            AddOptions {
+                priority: Some("x".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        );
+        assert!(matches!(result, Err(TdError::InvalidInput(_))));
+        assert_eq!(store.tasks().len(), 1);
+    }
+
+    #[test]
+    fn test_done_spawns_next_recurrence() {
+        let (_dir, mut store) = test_store();
+
+        add(
+            &mut store,
+            "Weekly report +status".to_string(),
+            AddOptions {
+                date: Some(today_str()),
+                every: Some("week".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        )
+        .unwrap();
+        mark_done(&mut store, 1, false).unwrap();
+
+        let next = store.find(2).unwrap().clone();
+        let expected = parse_date_str(&today_str()).unwrap() + chrono::Duration::weeks(1);
+        assert_eq!(next.date, expected.format("%Y-%m-%d").to_string());
+        assert_eq!(next.reuse_by, Some(1));
+        assert_eq!(next.tags, vec!["status"]);
+        assert_eq!(next.recurrence, Some(Recurrence::Weeks(1)));
+        assert!(!next.done);
+
+        // The lineage keeps pointing at the first occurrence
+        mark_done(&mut store, 2, false).unwrap();
+        let third = store.find(3).unwrap().clone();
+        assert_eq!(third.reuse_by, Some(1));
+
+        // Completing an already-done task doesn't spawn another one
+        mark_done(&mut store, 2, false).unwrap();
+        assert_eq!(store.max_id(), 3);
+    }
+
+    #[test]
+    fn test_relative_dates_are_stored_as_iso() {
+        let (_dir, mut store) = test_store();
+
+        add(
+            &mut store,
+            "Prepare demo".to_string(),
+            AddOptions {
+                date: Some("tomorrow".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        )
+        .unwrap();
+        let expected = parse_date_str(&today_str()).unwrap() + chrono::Duration::days(1);
+        let task = store.find(1).unwrap().clone();
+        assert_eq!(task.date, expected.format("%Y-%m-%d").to_string());
+
+        edit(
+            &mut store,
+            1,
+            EditOptions {
+                date: Some("+3d".to_string()),
+                ..Default::default()
+            },
+            &Config::default(),
+        )
+        .unwrap();
+        let expected = parse_date_str(&today_str()).unwrap() + chrono::Duration::days(3);
+        let task = store.find(1).unwrap().clone();
+        assert_eq!(task.date, expected.format("%Y-%m-%d").to_string());
+
+        assert!(resolve_date_str("someday", chrono::Weekday::Mon).is_err());
+    }
+
+    #[test]
+    fn test_filter_date_windows() {
+        let today = parse_date_str("2025-08-06").unwrap();
+        let window =
+            |filter: TaskFilter| match filter.date_window(today, &Config::default()).unwrap() {
+                DateWindow::Range { start, end, .. } => (start, end),
+                _ => panic!("expected a range"),
+            };
+        let date = |s: &str| Some(parse_date_str(s).unwrap());
+
+        let week = TaskFilter {
+            week: Some("2025-W31".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(window(week), (date("2025-07-28"), date("2025-08-03")));
+
+        let last_week = TaskFilter {
+            last_week: true,
+            ..Default::default()
+        };
+        assert_eq!(window(last_week), (date("2025-07-28"), date("2025-08-03")));
+
+        let month = TaskFilter {
+            month: Some("2025-02".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(window(month), (date("2025-02-01"), date("2025-02-28")));
+
+        let range = TaskFilter {
+            from_date: Some("2025-08-01".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(window(range), (date("2025-08-01"), None));
+
+        let overdue = TaskFilter {
+            overdue: true,
+            ..Default::default()
+        };
+        assert_eq!(window(overdue), (None, date("2025-08-05")));
+
+        // ISO weeks keep their Monday to Sunday range whatever the week start
+        let sunday_start = Config {
+            week_start: "sunday".to_string(),
+            ..Default::default()
+        };
+        let iso_week = TaskFilter {
+            week: Some("2025-W31".to_string()),
+            ..Default::default()
+        };
+        match iso_week.date_window(today, &sunday_start).unwrap() {
+            DateWindow::Range {
+                start,
+                end,
+                heading,
+            } => {
+                assert_eq!((start, end), (date("2025-07-28"), date("2025-08-03")));
+                assert!(heading.contains("Week 2025-W31"));
+            }
+            _ => panic!("expected a range"),
+        }
+        let this_week = TaskFilter {
+            week: Some("this".to_string()),
+            ..Default::default()
+        };
+        match this_week.date_window(today, &sunday_start).unwrap() {
+            DateWindow::Range { start, end, .. } => {
+                assert_eq!((start, end), (date("2025-08-03"), date("2025-08-09")))
+            }
+            _ => panic!("expected a range"),
+        }
+
+        let bad = TaskFilter {
+            week: Some("2025-W99".to_string()),
+            ..Default::default()
+        };
+        assert!(bad.date_window(today, &Config::default()).is_err());
+    }
+
+    #[test]
+    fn test_overdue_default_view() {
+        let (_dir, mut store) = test_store();
+        for (id, done) in [(1, false), (2, true)] {
+            store.add(Task {
+                id,
+                task: format!("Task {}", id),
+                date: "2025-01-01".to_string(),
+                done,
+                ..Default::default()
+            });
+        }
+        let config = Config {
+            default_view: ListView::Overdue,
+            ..Default::default()
+        };
+
+        // Same as `td list --overdue`: finished tasks aren't overdue
+        let ids = select_task_ids(&store, &TaskFilter::default(), &config).unwrap();
+        assert_eq!(ids, vec![1]);
+    }
+
+    #[test]
+    fn test_parse_id_args() {
+        let args = vec!["7".to_string(), "3,5".to_string(), "10-12".to_string()];
+        assert_eq!(parse_id_args(&args).unwrap(), vec![3, 5, 7, 10, 11, 12]);
+        assert_eq!(parse_id_args(&["2-2".to_string()]).unwrap(), vec![2]);
+        assert!(parse_id_args(&["5-3".to_string()]).is_err());
+        assert!(parse_id_args(&["abc".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_bulk_done_by_filter() {
+        let (_dir, mut store) = test_store();
+
+        for name in ["Standup", "Review PR", "standup notes"] {
+            add(
+                &mut store,
+                name.to_string(),
+                AddOptions::default(),
+                &Config::default(),
+            )
+            .unwrap();
+        }
+        let filter = TaskFilter {
+            search: Some("standup".to_string()),
+            ..Default::default()
+        };
+        let ids = select_task_ids(&store, &filter, &Config::default()).unwrap();
+        assert_eq!(ids, vec![1, 3]);
+
+        mark_done_many(&mut store, &ids, false).unwrap();
+        let done: Vec<usize> = store
+            .tasks()
+            .iter()
+            .filter(|t| t.done)
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(done, vec![1, 3]);
+
+        remove_many(&mut store, &[1, 2], true).unwrap();
+        assert_eq!(store.tasks().len(), 1);
+    }
+
     #[test]
     fn test_parse_date_str() {
         // Test successful parsing
@@ -0,0 +1,254 @@
+use crate::model::get_config_dir;
+use crate::storage::BackendKind;
+use anyhow::Result;
+use chrono::Weekday;
+use colored::Color;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+// Largest accepted review_days
+const MAX_REVIEW_DAYS: i64 = 36_500;
+
+// User settings from ~/.config/td-rs/config.toml. Every key is optional and
+// falls back to the built-in behaviour; unknown keys are rejected so typos
+// don't go unnoticed.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub icons: Icons,
+    pub colors: Colors,
+    // First day of the week for week ranges and "this week" views
+    pub week_start: String,
+    // What a bare `td list` shows
+    pub default_view: ListView,
+    // chrono format used when printing dates
+    pub date_format: String,
+    // `td review` shows tasks overdue by more than this many days;
+    // unset means everything from before the current week
+    pub review_days: Option<i64>,
+    // Width of the description column in `td review` and of {next} in the prompt
+    pub truncate_width: usize,
+    // Alternative location for todo.json; journal and reminders live next to it
+    pub storage_path: Option<PathBuf>,
+    // "json" (todo.json) or "sqlite" (todo.db next to it, imported from todo.json once)
+    pub storage_backend: BackendKind,
+    // Archive done tasks completed longer ago than this (e.g. "30d") whenever
+    // tasks are modified; unset leaves archiving to `td archive`
+    pub archive_after: Option<String>,
+}
+
+// Prompt icons, defaulting to Nerd Font glyphs
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Icons {
+    pub done: String,
+    pub high: String,
+    pub reused: String,
+    pub pending: String,
+    pub overdue: String,
+}
+
+// Prompt icon colours, by name (red, bright blue, ...)
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colors {
+    pub done: String,
+    pub high: String,
+    pub reused: String,
+    pub pending: String,
+    pub overdue: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ListView {
+    // Today's tasks plus this week's unfinished ones
+    #[default]
+    Today,
+    Week,
+    Month,
+    Overdue,
+    All,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            icons: Icons::default(),
+            colors: Colors::default(),
+            week_start: "monday".to_string(),
+            default_view: ListView::Today,
+            date_format: "%Y-%m-%d".to_string(),
+            review_days: None,
+            truncate_width: 22,
+            storage_path: None,
+            storage_backend: BackendKind::Json,
+            archive_after: None,
+        }
+    }
+}
+
+impl Default for Icons {
+    fn default() -> Self {
+        Icons {
+            done: "\u{f058}".to_string(),    // Nerd Font: NF-mdi-check
+            high: "\u{f06a}".to_string(),    // Nerd Font: NF-fa-exclamation_circle
+            reused: "\u{f46a}".to_string(),  // Nerd Font: NF-mdi-sync
+            pending: "\u{f096}".to_string(), // Nerd Font: NF-oct-tasklist
+            overdue: "\u{f071}".to_string(), // Nerd Font: NF-fa-warning
+        }
+    }
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            done: "green".to_string(),
+            high: "magenta".to_string(),
+            reused: "yellow".to_string(),
+            pending: "red".to_string(),
+            overdue: "bright red".to_string(),
+        }
+    }
+}
+
+impl Config {
+    // Checked when the config is loaded, so this can't fail afterwards
+    pub fn week_start(&self) -> Weekday {
+        self.week_start.parse().unwrap_or(Weekday::Mon)
+    }
+
+    pub fn format_date(&self, date: chrono::NaiveDate) -> String {
+        date.format(&self.date_format).to_string()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.week_start.parse::<Weekday>().is_err() {
+            return Err(anyhow::anyhow!(
+                "Invalid week_start '{}'. Use a weekday such as monday or sunday.",
+                self.week_start
+            ));
+        }
+        for color in [
+            &self.colors.done,
+            &self.colors.high,
+            &self.colors.reused,
+            &self.colors.pending,
+            &self.colors.overdue,
+        ] {
+            if color.parse::<Color>().is_err() {
+                return Err(anyhow::anyhow!("Unknown colour '{}'.", color));
+            }
+        }
+        // chrono would only panic on a bad format string once it's used
+        if chrono::format::StrftimeItems::new(&self.date_format)
+            .any(|item| matches!(item, chrono::format::Item::Error))
+        {
+            return Err(anyhow::anyhow!(
+                "Invalid date_format '{}'.",
+                self.date_format
+            ));
+        }
+        // About a century back is plenty, and keeps the cutoff a valid date
+        if self
+            .review_days
+            .is_some_and(|d| !(0..=MAX_REVIEW_DAYS).contains(&d))
+        {
+            return Err(anyhow::anyhow!(
+                "review_days must be between 0 and {}.",
+                MAX_REVIEW_DAYS
+            ));
+        }
+        if self.truncate_width == 0 {
+            return Err(anyhow::anyhow!("truncate_width must be at least 1."));
+        }
+        if let Some(after) = &self.archive_after {
+            let today = chrono::Local::now().date_naive();
+            crate::cli::parse_since(after, today, self.week_start())
+                .map_err(|e| anyhow::anyhow!("Invalid archive_after: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn get_config_path() -> Result<PathBuf> {
+    Ok(get_config_dir()?.join("config.toml"))
+}
+
+// Read the config file; a missing file means all defaults
+pub fn load_config() -> Result<Config> {
+    let path = get_config_path()?;
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let data = std::fs::read_to_string(&path)?;
+    let config = parse_config(&data).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+    Ok(config)
+}
+
+fn parse_config(data: &str) -> Result<Config> {
+    let mut config: Config = toml::from_str(data)?;
+    config.storage_path = config.storage_path.map(expand_home);
+    config.validate()?;
+    Ok(config)
+}
+
+// "~/Dropbox/todo.json" -> "/home/me/Dropbox/todo.json"
+fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = parse_config(
+            r#"
+week_start = "sunday"
+default_view = "week"
+date_format = "%d/%m/%Y"
+review_days = 14
+truncate_width = 40
+
+[icons]
+done = "x"
+
+[colors]
+pending = "bright blue"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.week_start(), Weekday::Sun);
+        assert_eq!(config.default_view, ListView::Week);
+        assert_eq!(config.review_days, Some(14));
+        assert_eq!(config.truncate_width, 40);
+        assert_eq!(config.icons.done, "x");
+        // Keys that aren't set keep their defaults
+        assert_eq!(config.icons.pending, Icons::default().pending);
+        assert_eq!(config.colors.done, "green");
+        assert_eq!(
+            config.format_date(chrono::NaiveDate::from_ymd_opt(2025, 8, 1).unwrap()),
+            "01/08/2025"
+        );
+
+        assert_eq!(parse_config("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(parse_config("week_start = \"someday\"").is_err());
+        assert!(parse_config("[colors]\ndone = \"sparkly\"").is_err());
+        assert!(parse_config("date_format = \"%Q\"").is_err());
+        assert!(parse_config("default_view = \"year\"").is_err());
+        assert!(parse_config("wek_start = \"monday\"").is_err());
+        assert!(parse_config("truncate_width = 0").is_err());
+        assert!(parse_config("review_days = -1").is_err());
+        assert!(parse_config("review_days = 100000000000").is_err());
+    }
+}
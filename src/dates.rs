@@ -0,0 +1,321 @@
+use crate::error::{Result, TdError};
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveTime, Weekday};
+
+// Resolve a user-supplied date relative to `today`.
+//
+// Accepts ISO dates (2025-08-01), keywords (today, tomorrow, yesterday),
+// weekday names (fri = the next Friday on or after today), "next <weekday>"
+// (that day in the following week), "next week" / "next month", offsets
+// (+3d, -1w, +2m, +1y; a bare number means days) and period ends (eow, eom, eoy).
+// Weeks begin on `week_start`.
+pub fn parse_natural_date(input: &str, today: NaiveDate, week_start: Weekday) -> Result<NaiveDate> {
+    let s = input.trim().to_lowercase();
+    let invalid = || {
+        TdError::InvalidDate(format!(
+            "Invalid date '{}'. Use YYYY-MM-DD, today, tomorrow, fri, +3d, next monday or eom.",
+            input
+        ))
+    };
+
+    if let Ok(date) = NaiveDate::parse_from_str(&s, "%Y-%m-%d") {
+        return Ok(date);
+    }
+
+    let keyword = match s.as_str() {
+        "today" => Some(today),
+        "tomorrow" | "tmr" => today.succ_opt(),
+        "yesterday" => today.pred_opt(),
+        "eow" => Some(start_of_week(today, week_start) + Duration::days(6)),
+        "eom" => last_day_of_month(today),
+        "eoy" => NaiveDate::from_ymd_opt(today.year(), 12, 31),
+        "next week" => Some(start_of_week(today, week_start) + Duration::weeks(1)),
+        "next month" => NaiveDate::from_ymd_opt(today.year(), today.month(), 1)
+            .and_then(|d| d.checked_add_months(Months::new(1))),
+        _ => None,
+    };
+    if let Some(date) = keyword {
+        return Ok(date);
+    }
+
+    if let Some(day) = s.strip_prefix("next ") {
+        let weekday: Weekday = day.trim().parse().map_err(|_| invalid())?;
+        let next_week = start_of_week(today, week_start) + Duration::weeks(1);
+        return Ok(next_week + Duration::days(weekday.days_since(week_start) as i64));
+    }
+
+    if let Ok(weekday) = s.parse::<Weekday>() {
+        let ahead =
+            (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+        return Ok(today + Duration::days(ahead as i64));
+    }
+
+    if s.starts_with('+') || s.starts_with('-') {
+        return parse_offset(&s, today).ok_or_else(invalid);
+    }
+
+    Err(invalid())
+}
+
+// A time of day such as 14:30, 9:05, 2pm or 2:30pm
+pub fn parse_time_of_day(input: &str) -> Result<NaiveTime> {
+    let s = input.trim().to_lowercase();
+    let invalid = || {
+        TdError::InvalidInput(format!(
+            "Invalid time '{}'. Use HH:MM (24h) or e.g. 2:30pm.",
+            input
+        ))
+    };
+
+    let (clock, pm) = match (s.strip_suffix("am"), s.strip_suffix("pm")) {
+        (Some(rest), _) => (rest.trim(), Some(false)),
+        (_, Some(rest)) => (rest.trim(), Some(true)),
+        _ => (s.as_str(), None),
+    };
+    let (hour, minute) = clock.split_once(':').unwrap_or((clock, "0"));
+    let mut hour: u32 = hour.parse().map_err(|_| invalid())?;
+    let minute: u32 = minute.parse().map_err(|_| invalid())?;
+    if let Some(pm) = pm {
+        if !(1..=12).contains(&hour) {
+            return Err(invalid());
+        }
+        hour = hour % 12 + if pm { 12 } else { 0 };
+    } else if !clock.contains(':') {
+        // A bare number is only accepted with am/pm
+        return Err(invalid());
+    }
+    NaiveTime::from_hms_opt(hour, minute, 0).ok_or_else(invalid)
+}
+
+// A duration such as 45m, 2h, 1h30m or 90 (minutes), in minutes
+pub fn parse_duration_minutes(input: &str) -> Result<u32> {
+    let s = input.trim().to_lowercase();
+    let invalid = || {
+        TdError::InvalidInput(format!(
+            "Invalid duration '{}'. Use e.g. 45m, 2h or 1h30m.",
+            input
+        ))
+    };
+
+    if let Ok(minutes) = s.parse::<u32>() {
+        return Ok(minutes);
+    }
+    let (hours, rest) = match s.split_once('h') {
+        Some((h, rest)) => (h.parse::<u32>().map_err(|_| invalid())?, rest),
+        None => (0, s.as_str()),
+    };
+    let minutes = match rest.strip_suffix('m') {
+        Some(m) => m.parse::<u32>().map_err(|_| invalid())?,
+        None if rest.is_empty() => 0,
+        None => return Err(invalid()),
+    };
+    let total = hours
+        .checked_mul(60)
+        .and_then(|h| h.checked_add(minutes))
+        .ok_or_else(invalid)?;
+    if total == 0 {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+// Compact rendering of a number of minutes: 25m, 1h, 1h30m
+pub fn format_minutes(minutes: i64) -> String {
+    let (hours, minutes) = (minutes / 60, minutes % 60);
+    match (hours, minutes) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h{}m", h, m),
+    }
+}
+
+// "+3d", "-2w", "+1m", "+1y" or "+3" (days)
+fn parse_offset(s: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let (negative, rest) = match s.split_at(1) {
+        ("-", rest) => (true, rest),
+        (_, rest) => (false, rest),
+    };
+    let unit_at = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let (num, unit) = rest.split_at(unit_at);
+    let n: u32 = num.parse().ok()?;
+
+    match unit {
+        "" | "d" | "w" => {
+            let offset = if unit == "w" {
+                Duration::try_weeks(n as i64)?
+            } else {
+                Duration::try_days(n as i64)?
+            };
+            if negative {
+                today.checked_sub_signed(offset)
+            } else {
+                today.checked_add_signed(offset)
+            }
+        }
+        "m" | "y" => {
+            let months = Months::new(if unit == "y" { n.checked_mul(12)? } else { n });
+            if negative {
+                today.checked_sub_months(months)
+            } else {
+                today.checked_add_months(months)
+            }
+        }
+        _ => None,
+    }
+}
+
+fn start_of_week(date: NaiveDate, week_start: Weekday) -> NaiveDate {
+    date - Duration::days(date.weekday().days_since(week_start) as i64)
+}
+
+fn last_day_of_month(date: NaiveDate) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(date.year(), date.month(), 1)?
+        .checked_add_months(Months::new(1))?
+        .pred_opt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_keywords_and_offsets() {
+        // 2025-08-06 is a Wednesday
+        let today = date("2025-08-06");
+
+        assert_eq!(
+            parse_natural_date("2025-01-02", today, Weekday::Mon).unwrap(),
+            date("2025-01-02")
+        );
+        assert_eq!(
+            parse_natural_date("Tomorrow", today, Weekday::Mon).unwrap(),
+            date("2025-08-07")
+        );
+        assert_eq!(
+            parse_natural_date("yesterday", today, Weekday::Mon).unwrap(),
+            date("2025-08-05")
+        );
+        assert_eq!(
+            parse_natural_date("+3d", today, Weekday::Mon).unwrap(),
+            date("2025-08-09")
+        );
+        assert_eq!(
+            parse_natural_date("+3", today, Weekday::Mon).unwrap(),
+            date("2025-08-09")
+        );
+        assert_eq!(
+            parse_natural_date("-1w", today, Weekday::Mon).unwrap(),
+            date("2025-07-30")
+        );
+        assert_eq!(
+            parse_natural_date("+1m", today, Weekday::Mon).unwrap(),
+            date("2025-09-06")
+        );
+        assert_eq!(
+            parse_natural_date("eow", today, Weekday::Mon).unwrap(),
+            date("2025-08-10")
+        );
+        assert_eq!(
+            parse_natural_date("eom", today, Weekday::Mon).unwrap(),
+            date("2025-08-31")
+        );
+        assert_eq!(
+            parse_natural_date("eoy", today, Weekday::Mon).unwrap(),
+            date("2025-12-31")
+        );
+        assert_eq!(
+            parse_natural_date("next month", today, Weekday::Mon).unwrap(),
+            date("2025-09-01")
+        );
+    }
+
+    #[test]
+    fn test_weekdays() {
+        let today = date("2025-08-06");
+
+        assert_eq!(
+            parse_natural_date("fri", today, Weekday::Mon).unwrap(),
+            date("2025-08-08")
+        );
+        assert_eq!(
+            parse_natural_date("wednesday", today, Weekday::Mon).unwrap(),
+            today
+        );
+        assert_eq!(
+            parse_natural_date("mon", today, Weekday::Mon).unwrap(),
+            date("2025-08-11")
+        );
+        assert_eq!(
+            parse_natural_date("next monday", today, Weekday::Mon).unwrap(),
+            date("2025-08-11")
+        );
+        assert_eq!(
+            parse_natural_date("next fri", today, Weekday::Mon).unwrap(),
+            date("2025-08-15")
+        );
+        assert_eq!(
+            parse_natural_date("next week", today, Weekday::Mon).unwrap(),
+            date("2025-08-11")
+        );
+        // With weeks starting on Sunday, this week is 2025-08-03 to 2025-08-09
+        assert_eq!(
+            parse_natural_date("eow", today, Weekday::Sun).unwrap(),
+            date("2025-08-09")
+        );
+        assert_eq!(
+            parse_natural_date("next week", today, Weekday::Sun).unwrap(),
+            date("2025-08-10")
+        );
+        assert_eq!(
+            parse_natural_date("next sat", today, Weekday::Sun).unwrap(),
+            date("2025-08-16")
+        );
+    }
+
+    #[test]
+    fn test_times_and_durations() {
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        assert_eq!(parse_time_of_day("14:30").unwrap(), time(14, 30));
+        assert_eq!(parse_time_of_day("9:05").unwrap(), time(9, 5));
+        assert_eq!(parse_time_of_day("2pm").unwrap(), time(14, 0));
+        assert_eq!(parse_time_of_day("12:15am").unwrap(), time(0, 15));
+        assert!(parse_time_of_day("25:00").is_err());
+        assert!(parse_time_of_day("14").is_err());
+        assert!(parse_time_of_day("13pm").is_err());
+
+        assert_eq!(parse_duration_minutes("45m").unwrap(), 45);
+        assert_eq!(parse_duration_minutes("2h").unwrap(), 120);
+        assert_eq!(parse_duration_minutes("1h30m").unwrap(), 90);
+        assert_eq!(parse_duration_minutes("90").unwrap(), 90);
+        assert!(parse_duration_minutes("0m").is_err());
+        assert!(parse_duration_minutes("soon").is_err());
+
+        assert_eq!(format_minutes(25), "25m");
+        assert_eq!(format_minutes(60), "1h");
+        assert_eq!(format_minutes(95), "1h35m");
+    }
+
+    #[test]
+    fn test_invalid_dates() {
+        let today = date("2025-08-06");
+
+        assert!(parse_natural_date("someday", today, Weekday::Mon).is_err());
+        assert!(parse_natural_date("+3x", today, Weekday::Mon).is_err());
+        assert!(parse_natural_date("next blursday", today, Weekday::Mon).is_err());
+        assert!(parse_natural_date("2025-02-30", today, Weekday::Mon).is_err());
+
+        // Offsets past the representable range are errors, not panics
+        for offset in ["+99999999d", "-99999999w", "+4294967295w", "+999999999y"] {
+            assert!(matches!(
+                parse_natural_date(offset, today, Weekday::Mon),
+                Err(TdError::InvalidDate(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use crate::archive;
+use crate::cli::{confirm, parse_timestamp};
+use crate::error::{Result, TdError};
+use crate::model::{today_str, Task};
+use crate::storage::StorageBackend;
+use crate::task_store::Store;
+use chrono::NaiveDate;
+use colored::Colorize;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+// Fix what would make the store refuse to load, in place, keeping every
+// task: duplicate and zero IDs get new ones from `next_id` up, bad dates are
+// replaced and `reuse_by` links to tasks that are gone are dropped. Returns
+// a description of each problem and its fix.
+fn repair(
+    tasks: &mut [Task],
+    mut next_id: usize,
+    is_archived: impl Fn(usize) -> bool,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    // Checked before renumbering, which could hand a missing ID to another task
+    let ids: HashSet<usize> = tasks.iter().map(|t| t.id).collect();
+    for t in tasks.iter_mut() {
+        if let Some(by) = t.reuse_by {
+            if !ids.contains(&by) && !is_archived(by) {
+                problems.push(format!(
+                    "Task #{} was reused by #{}, which doesn't exist; the link is removed",
+                    t.id, by
+                ));
+                t.reuse_by = None;
+            }
+        }
+    }
+
+    // The first task with an ID keeps it, so links to that ID still point
+    // where they did before the duplicate was added
+    let mut seen = HashSet::new();
+    for t in tasks.iter_mut() {
+        if t.id == 0 || !seen.insert(t.id) {
+            let reason = if t.id == 0 {
+                "has the invalid ID 0"
+            } else {
+                "has the same ID as an earlier task"
+            };
+            problems.push(format!(
+                "Task #{} \"{}\" {}; it becomes #{}",
+                t.id, t.task, reason, next_id
+            ));
+            t.id = next_id;
+            seen.insert(next_id);
+            next_id += 1;
+        }
+    }
+
+    for t in tasks.iter_mut() {
+        if NaiveDate::parse_from_str(&t.date, "%Y-%m-%d").is_ok() {
+            continue;
+        }
+        // The day it was created is the best guess at what was meant
+        let date = t
+            .created_at
+            .as_deref()
+            .and_then(parse_timestamp)
+            .map_or_else(today_str, |at| at.format("%Y-%m-%d").to_string());
+        problems.push(format!(
+            "Task #{} has the invalid date \"{}\"; it is moved to {}",
+            t.id, t.date, date
+        ));
+        t.date = date;
+    }
+
+    problems
+}
+
+// Each repaired task as it is stored and as it would be written, showing
+// just the fields that change
+fn print_diff(before: &[Task], after: &[Task]) {
+    for (old, new) in before.iter().zip(after).filter(|(old, new)| old != new) {
+        let (Ok(Value::Object(old)), Ok(Value::Object(new))) =
+            (serde_json::to_value(old), serde_json::to_value(new))
+        else {
+            continue;
+        };
+        let changed = |fields: &Map<String, Value>| -> Map<String, Value> {
+            fields
+                .iter()
+                .filter(|(key, _)| old.get(*key) != new.get(*key) || *key == "task")
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect()
+        };
+        println!("{}", format!("- {}", Value::Object(changed(&old))).red());
+        println!("{}", format!("+ {}", Value::Object(changed(&new))).green());
+    }
+}
+
+// Check the store for damage and offer to write back what can be recovered.
+// Nothing is written without confirmation, and the previous data is kept as
+// a timestamped backup.
+pub fn doctor(backend: Box<dyn StorageBackend>, assume_yes: bool) -> Result<()> {
+    let mut store = Store::open_for_repair(backend)?;
+    let path = store.path().to_path_buf();
+    println!("Checking {}", path.display());
+
+    let mut problems = Vec::new();
+    let before = match store.load_raw() {
+        Ok(tasks) => tasks,
+        // The stored data doesn't read as a whole; keep the tasks that are
+        // still intact. That may be another file, e.g. the todo.json a new
+        // SQLite database is set up from.
+        Err(TdError::Corrupt {
+            path: damaged,
+            reason,
+        }) => {
+            let Some(salvage) = store.salvage()? else {
+                return Err(TdError::Corrupt {
+                    path: damaged,
+                    reason: format!(
+                        "{}. td doctor can't recover tasks from this storage backend",
+                        reason
+                    ),
+                });
+            };
+            problems.push(format!(
+                "{} can't be read ({}); {} task(s) were recovered from it",
+                damaged.display(),
+                reason,
+                salvage.tasks.len()
+            ));
+            if salvage.lost > 0 {
+                problems.push(format!(
+                    "{} task(s) are too damaged to recover and will be dropped",
+                    salvage.lost
+                ));
+            }
+            salvage.tasks
+        }
+        Err(e) => return Err(e),
+    };
+
+    let next_id = before
+        .iter()
+        .map(|t| t.id)
+        .max()
+        .unwrap_or(0)
+        .max(archive::max_archived_id(&store)?)
+        + 1;
+    let mut after = before.clone();
+    problems.extend(repair(&mut after, next_id, |id| {
+        matches!(archive::find_archived_task(&store, id), Ok(Some(_)))
+    }));
+
+    if problems.is_empty() {
+        println!("No problems found.");
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("  - {}", problem);
+    }
+    print_diff(&before, &after);
+
+    let timestamp = chrono::Local::now().format("%Y-%m-%d-%H%M%S");
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let backup = path.with_file_name(format!("{}.{}.bak", file_name, timestamp));
+    if !assume_yes && !confirm(&format!("Write {} task(s)?", after.len()))? {
+        println!("Aborted. Nothing was changed.");
+        return Ok(());
+    }
+    store.replace_all(after, &backup)?;
+    println!(
+        "Repaired {}; the previous version was kept as {}.",
+        path.display(),
+        backup.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: usize, date: &str) -> Task {
+        Task {
+            id,
+            task: format!("Task {}", id),
+            date: date.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_repair_keeps_every_task() {
+        let mut tasks = vec![
+            task(1, "2025-08-01"),
+            task(2, "2025-08-01"),
+            task(1, "2025-08-02"),
+            task(0, "2025-08-03"),
+            Task {
+                created_at: Some("2025-08-04T12:00:00+00:00".to_string()),
+                reuse_by: Some(9),
+                ..task(3, "2025-13-01")
+            },
+            Task {
+                reuse_by: Some(7),
+                ..task(4, "2025-08-05")
+            },
+        ];
+        let problems = repair(&mut tasks, 8, |id| id == 7);
+
+        assert_eq!(problems.len(), 4);
+        let ids: Vec<usize> = tasks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 2, 8, 9, 3, 4]);
+        assert_eq!(tasks[2].date, "2025-08-02");
+        assert_eq!(tasks[4].date, "2025-08-04");
+        // Dangling even though the zero-ID task was renumbered to #9
+        assert_eq!(tasks[4].reuse_by, None);
+        // Archived tasks aren't dangling
+        assert_eq!(tasks[5].reuse_by, Some(7));
+
+        let mut tasks = vec![Task {
+            reuse_by: Some(5),
+            ..task(1, "2025-08-01")
+        }];
+        assert_eq!(repair(&mut tasks, 2, |_| false).len(), 1);
+        assert_eq!(tasks[0].reuse_by, None);
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use std::path::PathBuf;
+
+pub type Result<T, E = TdError> = std::result::Result<T, E>;
+
+// Why a td command failed. Each kind maps to its own exit code, so scripts
+// can tell "no such task" apart from "the store is busy".
+#[derive(Debug)]
+pub enum TdError {
+    // No task with this ID
+    NotFound(usize),
+    // A date, week, month or lookback that couldn't be understood
+    InvalidDate(String),
+    // Any other rejected argument, or a request that can't be carried out as given
+    InvalidInput(String),
+    // Another td process held the store lock for too long
+    StoreLocked(PathBuf),
+    // A data file that exists but couldn't be read as td data
+    Corrupt { path: PathBuf, reason: String },
+    // A todo.json written by a newer td in a format this one doesn't know
+    UnsupportedVersion { path: PathBuf, version: u64 },
+    Io(std::io::Error),
+    Other(anyhow::Error),
+}
+
+// Exit status of `td count --exit-code` when nothing matches, kept apart from
+// the error codes so scripts can tell "no tasks" from "td failed"
+pub const NO_MATCHES_EXIT_CODE: i32 = 9;
+
+impl TdError {
+    // Exit status for `td`; see "Exit Codes" in the README
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            TdError::Other(_) => 1,
+            TdError::InvalidInput(_) => 2,
+            TdError::InvalidDate(_) => 3,
+            TdError::NotFound(_) => 4,
+            TdError::StoreLocked(_) => 5,
+            TdError::Corrupt { .. } => 6,
+            TdError::Io(_) => 7,
+            TdError::UnsupportedVersion { .. } => 8,
+        }
+    }
+
+    pub fn invalid_input(message: impl fmt::Display) -> TdError {
+        TdError::InvalidInput(message.to_string())
+    }
+}
+
+impl fmt::Display for TdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TdError::NotFound(id) => write!(f, "Task #{} not found.", id),
+            TdError::InvalidDate(message) | TdError::InvalidInput(message) => {
+                write!(f, "{}", message)
+            }
+            TdError::StoreLocked(path) => {
+                write!(f, "Task store is locked by another td process ({:?})", path)
+            }
+            TdError::Corrupt { path, reason } => {
+                write!(f, "Could not read {}: {}", path.display(), reason)
+            }
+            TdError::UnsupportedVersion { path, version } => write!(
+                f,
+                "{} was written by a newer td (format version {}, this td reads up to {}). \
+                 Upgrade td to use it.",
+                path.display(),
+                version,
+                crate::model::SCHEMA_VERSION
+            ),
+            TdError::Io(e) => write!(f, "{}", e),
+            TdError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for TdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TdError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for TdError {
+    fn from(e: std::io::Error) -> Self {
+        TdError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for TdError {
+    fn from(e: serde_json::Error) -> Self {
+        TdError::Other(e.into())
+    }
+}
+
+impl From<chrono::ParseError> for TdError {
+    fn from(e: chrono::ParseError) -> Self {
+        TdError::Other(e.into())
+    }
+}
+
+// Helpers below the command layer still use anyhow; a `TdError` (or I/O error)
+// they pass along keeps its kind
+impl From<anyhow::Error> for TdError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<TdError>() {
+            Ok(e) => e,
+            Err(e) => match e.downcast::<std::io::Error>() {
+                Ok(e) => TdError::Io(e),
+                Err(e) => TdError::Other(e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_survives_anyhow() {
+        let wrapped: anyhow::Error = TdError::StoreLocked(PathBuf::from("todo.json.lock")).into();
+        assert!(matches!(TdError::from(wrapped), TdError::StoreLocked(_)));
+
+        let io: anyhow::Error = std::io::Error::other("disk full").into();
+        assert_eq!(TdError::from(io).exit_code(), 7);
+
+        let other = TdError::from(anyhow::anyhow!("something else"));
+        assert_eq!(other.exit_code(), 1);
+        assert_eq!(TdError::NotFound(3).to_string(), "Task #3 not found.");
+    }
+}
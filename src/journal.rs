@@ -0,0 +1,147 @@
+use crate::error::{Result, TdError};
+use crate::model::{write_atomic, Task};
+use crate::task_store::Store;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+// Oldest entries are dropped once the undo history grows past this
+const MAX_HISTORY: usize = 100;
+
+// One task's state before and after a command; `None` means the task didn't exist
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Change {
+    pub id: usize,
+    pub before: Option<Task>,
+    pub after: Option<Task>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalEntry {
+    pub command: String,
+    pub timestamp: String,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Journal {
+    #[serde(default)]
+    pub undo: Vec<JournalEntry>,
+    #[serde(default)]
+    pub redo: Vec<JournalEntry>,
+}
+
+impl Journal {
+    // Record a new command; any redo history is discarded
+    pub fn push(&mut self, entry: JournalEntry) {
+        self.undo.push(entry);
+        if self.undo.len() > MAX_HISTORY {
+            let excess = self.undo.len() - MAX_HISTORY;
+            self.undo.drain(..excess);
+        }
+        self.redo.clear();
+    }
+}
+
+pub fn get_journal_path(store: &Store) -> PathBuf {
+    store.sibling_path("journal.json")
+}
+
+pub fn load_journal(store: &Store) -> Result<Journal> {
+    let path = get_journal_path(store);
+    if !path.exists() {
+        return Ok(Journal::default());
+    }
+
+    let data = fs::read_to_string(&path)?;
+    let journal = serde_json::from_str(&data).map_err(|e| TdError::Corrupt {
+        path: path.clone(),
+        reason: e.to_string(),
+    })?;
+    Ok(journal)
+}
+
+pub fn save_journal(store: &Store, journal: &Journal) -> Result<()> {
+    let path = get_journal_path(store);
+    let json = serde_json::to_string_pretty(journal)?;
+    Ok(write_atomic(&path, json.as_bytes())?)
+}
+
+// Per-task differences between two versions of the task list, ordered by ID
+pub fn diff_tasks(before: &[Task], after: &[Task]) -> Vec<Change> {
+    let old: HashMap<usize, &Task> = before.iter().map(|t| (t.id, t)).collect();
+    let new: HashMap<usize, &Task> = after.iter().map(|t| (t.id, t)).collect();
+    let mut ids: Vec<usize> = old.keys().chain(new.keys()).copied().collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    ids.into_iter()
+        .filter_map(|id| {
+            let old = old.get(&id).copied();
+            let new = new.get(&id).copied();
+            if old == new {
+                None
+            } else {
+                Some(Change {
+                    id,
+                    before: old.cloned(),
+                    after: new.cloned(),
+                })
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: usize, name: &str, done: bool) -> Task {
+        Task {
+            id,
+            task: name.to_string(),
+            date: "2025-08-01".to_string(),
+            done,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff_tasks() {
+        let before = vec![
+            task(1, "Keep", false),
+            task(2, "Finish", false),
+            task(3, "Drop", false),
+        ];
+        let after = vec![
+            task(1, "Keep", false),
+            task(2, "Finish", true),
+            task(4, "New", false),
+        ];
+
+        let changes = diff_tasks(&before, &after);
+        let ids: Vec<usize> = changes.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![2, 3, 4]);
+        assert!(changes[0].after.as_ref().unwrap().done);
+        assert!(changes[1].after.is_none());
+        assert!(changes[2].before.is_none());
+    }
+
+    #[test]
+    fn test_push_clears_redo_and_caps_history() {
+        let entry = |n: usize| JournalEntry {
+            command: format!("done {}", n),
+            timestamp: String::new(),
+            changes: vec![],
+        };
+        let mut journal = Journal::default();
+        journal.redo.push(entry(0));
+        for n in 0..MAX_HISTORY + 5 {
+            journal.push(entry(n));
+        }
+        assert!(journal.redo.is_empty());
+        assert_eq!(journal.undo.len(), MAX_HISTORY);
+        assert_eq!(journal.undo[0].command, "done 5");
+    }
+}
@@ -1,9 +1,27 @@
 // Public API for the td application
+pub mod archive;
 pub mod cli;
+pub mod config;
+pub mod dates;
+pub mod doctor;
+pub mod error;
+pub mod journal;
 pub mod model;
+pub mod prompt_cache;
+pub mod remind;
+pub mod storage;
 pub mod task_store;
 
 // Re-export common functionality
+pub use archive::*;
 pub use cli::*;
+pub use config::*;
+pub use dates::*;
+pub use doctor::*;
+pub use error::*;
+pub use journal::*;
 pub use model::*;
+pub use prompt_cache::*;
+pub use remind::*;
+pub use storage::*;
 pub use task_store::*;
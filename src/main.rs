@@ -1,75 +1,104 @@
-use clap::{Arg, Command};
+use clap::{Arg, ArgMatches, Command};
+use td::{archive, cli, config, doctor, error, model, remind, storage, task_store};
 
-mod cli;
-mod model;
 mod shell;
-mod task_store;
 
 fn main() {
-    if let Err(e) = task_store::TaskStore::init() {
-        eprintln!("Error initializing task store: {}", e);
-        std::process::exit(1);
-    }
-
     let matches = Command::new("td")
         .version("0.1.0")
         .about("Minimalistic CLI Todo")
         .subcommand(
             Command::new("add")
-                .about("Add a new task")
+                .about("Add a new task (use +tag and @project inline)")
                 .arg(Arg::new("task").required(true))
-                .arg(Arg::new("date").long("date").value_name("DATE")),
-        )
-        .subcommand(
-            Command::new("list")
-                .about("List tasks")
+                .arg(Arg::new("date").long("date").value_name("DATE"))
                 .arg(
-                    Arg::new("date")
-                        .long("date")
-                        .value_name("DATE")
-                        .help("List tasks for a specific date (YYYY-MM-DD)"),
+                    Arg::new("tag")
+                        .long("tag")
+                        .value_name("TAG")
+                        .action(clap::ArgAction::Append)
+                        .help("Tag the task (can be repeated)"),
                 )
                 .arg(
-                    Arg::new("week")
-                        .long("week")
-                        .action(clap::ArgAction::SetTrue)
-                        .help("List tasks for the current week"),
+                    Arg::new("project")
+                        .long("project")
+                        .value_name("PROJECT")
+                        .help("Assign the task to a project"),
                 )
                 .arg(
-                    Arg::new("month")
-                        .long("month")
-                        .action(clap::ArgAction::SetTrue)
-                        .help("List tasks for the current month"),
+                    Arg::new("priority")
+                        .long("priority")
+                        .short('p')
+                        .value_name("PRIORITY")
+                        .help("Task priority (H/M/L or 1-3)"),
                 )
                 .arg(
-                    Arg::new("done")
-                        .long("done")
-                        .action(clap::ArgAction::SetTrue)
-                        .help("List only completed tasks"),
+                    Arg::new("every")
+                        .long("every")
+                        .value_name("RULE")
+                        .help("Repeat the task: day, weekday, week, month, 2w, 3d or mon,thu"),
                 )
                 .arg(
-                    Arg::new("pending")
-                        .long("pending")
-                        .action(clap::ArgAction::SetTrue)
-                        .help("List only pending tasks"),
+                    Arg::new("parent")
+                        .long("parent")
+                        .value_name("ID")
+                        .help("Add the task as a subtask of another task"),
                 )
                 .arg(
-                    Arg::new("from_id")
-                        .long("from")
-                        .value_name("ID")
-                        .help("List tasks with ID greater than or equal to specified value"),
+                    Arg::new("at")
+                        .long("at")
+                        .value_name("TIME")
+                        .help("Time of day the task is due, e.g. 14:30 or 2pm"),
                 )
                 .arg(
-                    Arg::new("to_id")
-                        .long("to")
-                        .value_name("ID")
-                        .help("List tasks with ID less than or equal to specified value"),
+                    Arg::new("for")
+                        .long("for")
+                        .value_name("DURATION")
+                        .help("How long the task takes, e.g. 30m or 1h30m"),
+                ),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List tasks")
+                .args(filter_args())
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Output tasks in JSON format"),
                 )
                 .arg(
-                    Arg::new("search")
-                        .long("search")
-                        .value_name("KEYWORD")
-                        .help("Search tasks by keyword in task content"),
+                    Arg::new("times")
+                        .long("times")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Show when tasks were created and completed"),
+                )
+                .arg(
+                    Arg::new("archived")
+                        .long("archived")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Include tasks moved out by `td archive`"),
+                ),
+        )
+        .subcommand(
+            Command::new("count")
+                .about("Print the number of pending tasks matching the list filters")
+                .args(filter_args())
+                .arg(
+                    Arg::new("exit-code")
+                        .long("exit-code")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Exit with status 9 when no tasks match"),
+                ),
+        )
+        .subcommand(
+            Command::new("log")
+                .about("Show tasks completed recently, grouped by day")
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .value_name("WHEN")
+                        .help("Start of the log: 7d, 2w, 1m or a date (default: 7d)"),
                 )
                 .arg(
                     Arg::new("json")
@@ -80,18 +109,33 @@ fn main() {
         )
         .subcommand(
             Command::new("done")
-                .about("Mark task as done")
-                .arg(Arg::new("id").required(true)),
+                .about("Mark tasks as done by ID, range (3 5 10-20) or filter")
+                .arg(Arg::new("id").num_args(0..))
+                .args(filter_args())
+                .arg(
+                    Arg::new("cascade")
+                        .long("cascade")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Also complete open subtasks"),
+                ),
         )
         .subcommand(
             Command::new("rm")
-                .about("Remove task")
-                .arg(Arg::new("id").required(true)),
+                .about("Remove tasks by ID, range (3 5 10-20) or filter")
+                .arg(Arg::new("id").num_args(0..))
+                .args(filter_args())
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Remove multiple tasks without asking for confirmation"),
+                ),
         )
         .subcommand(
             Command::new("edit")
-                .about("Edit a task")
-                .arg(Arg::new("id").required(true))
+                .about("Edit tasks by ID or range")
+                .arg(Arg::new("id").required(true).num_args(1..))
                 .arg(
                     Arg::new("task")
                         .long("task")
@@ -103,21 +147,168 @@ fn main() {
                         .long("date")
                         .short('d')
                         .help("The new date of the task"),
+                )
+                .arg(
+                    Arg::new("priority")
+                        .long("priority")
+                        .short('p')
+                        .value_name("PRIORITY")
+                        .help("The new priority of the task (H/M/L, 1-3 or none)"),
+                )
+                .arg(
+                    Arg::new("every")
+                        .long("every")
+                        .value_name("RULE")
+                        .help("The new recurrence rule of the task (or none)"),
+                )
+                .arg(
+                    Arg::new("at")
+                        .long("at")
+                        .value_name("TIME")
+                        .help("The new due time of the task (or none)"),
+                )
+                .arg(
+                    Arg::new("for")
+                        .long("for")
+                        .value_name("DURATION")
+                        .help("The new duration of the task (or none)"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .value_name("TAG")
+                        .action(clap::ArgAction::Append)
+                        .help("Replace the task's tags (can be repeated, or none)"),
+                )
+                .arg(
+                    Arg::new("project")
+                        .long("project")
+                        .value_name("PROJECT")
+                        .help("The new project of the task (or none)"),
+                ),
+        )
+        .subcommand(
+            Command::new("prompt-today")
+                .about("Print status icons for prompt")
+                .arg(Arg::new("tag").long("tag").value_name("TAG"))
+                .arg(Arg::new("project").long("project").value_name("PROJECT"))
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_name("TEMPLATE")
+                        .help("Summary template, e.g. '{pending}/{total} {overdue_icon}{overdue}'"),
+                )
+                .arg(
+                    Arg::new("task-format")
+                        .long("task-format")
+                        .value_name("TEMPLATE")
+                        .help("Template for each task in {tasks} (default '{icon} #{id}')"),
                 ),
         )
-        .subcommand(Command::new("prompt-today").about("Print status icons for prompt"))
-        .subcommand(Command::new("review").about("Review tasks overdue by more than 7 days"))
+        .subcommand(
+            Command::new("review")
+                .about("Review overdue tasks (before this week, or review_days in config.toml)")
+                .arg(Arg::new("tag").long("tag").value_name("TAG"))
+                .arg(Arg::new("project").long("project").value_name("PROJECT")),
+        )
         .subcommand(
             Command::new("reuse")
-                .about("Reuse an existing task by ID, optionally with a new date")
-                .arg(Arg::new("id").required(true))
+                .about("Reuse existing tasks by ID or range, optionally with a new date")
+                .arg(Arg::new("id").required(true).num_args(1..))
                 .arg(
                     Arg::new("date")
                         .long("date")
                         .value_name("DATE")
-                        .help("New date for the reused task (YYYY-MM-DD)"),
+                        .help("New date for the reused task (YYYY-MM-DD or e.g. fri, +3d)"),
+                ),
+        )
+        .subcommand(
+            Command::new("block")
+                .about("Mark tasks as depending on other tasks")
+                .arg(Arg::new("id").required(true).num_args(1..))
+                .arg(
+                    Arg::new("on")
+                        .long("on")
+                        .value_name("ID")
+                        .required(true)
+                        .num_args(1..)
+                        .help("Tasks that must be finished first"),
+                ),
+        )
+        .subcommand(
+            Command::new("unblock")
+                .about("Remove task dependencies")
+                .arg(Arg::new("id").required(true).num_args(1..))
+                .arg(
+                    Arg::new("on")
+                        .long("on")
+                        .value_name("ID")
+                        .num_args(1..)
+                        .help("Only remove these dependencies (default: all)"),
                 ),
         )
+        .subcommand(
+            Command::new("note")
+                .about("Edit a task's notes in $EDITOR")
+                .arg(Arg::new("id").required(true)),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("Show a task with its notes, dates and history")
+                .arg(Arg::new("id").required(true)),
+        )
+        .subcommand(
+            Command::new("remind")
+                .about("Send reminders for tasks whose due time is coming up")
+                .arg(
+                    Arg::new("watch")
+                        .long("watch")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Keep running and check periodically"),
+                )
+                .arg(
+                    Arg::new("before")
+                        .long("before")
+                        .value_name("DURATION")
+                        .help("How long before the due time to remind (default: 10m)"),
+                )
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("30")
+                        .help("Seconds between checks with --watch"),
+                )
+                .arg(
+                    Arg::new("notify")
+                        .long("notify")
+                        .value_name("COMMAND")
+                        .help(
+                            "Command run with title and body, e.g. notify-send (default: stdout)",
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("archive")
+                .about("Move completed tasks into archive/YYYY-MM.json files")
+                .arg(Arg::new("before").long("before").value_name("WHEN").help(
+                    "Archive tasks completed before this: 30d, 2w, 3m or a date (default: 30d)",
+                )),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check the task list for damage and repair what can be recovered")
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Write the repaired tasks without asking for confirmation"),
+                ),
+        )
+        .subcommand(Command::new("undo").about("Undo the last change to the task list"))
+        .subcommand(Command::new("redo").about("Redo the last undone change"))
         .subcommand(
             Command::new("init")
                 .about("Print shell integration script")
@@ -125,95 +316,216 @@ fn main() {
         )
         .get_matches();
 
+    // Settings are read once and passed to the commands that display tasks
+    let config = match config::load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error loading config: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let storage_path = match &config.storage_path {
+        Some(path) => path.clone(),
+        None => match model::get_storage_path() {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("Error opening task storage: {}", e);
+                std::process::exit(1);
+            }
+        },
+    };
+    // Commands that only read tasks skip the store lock and never save
+    let read_only = matches!(
+        matches.subcommand_name(),
+        None | Some(
+            "list" | "count" | "log" | "show" | "review" | "remind" | "init" | "prompt-today"
+        )
+    );
+    let backend = match storage::open_backend(config.storage_backend, &storage_path, read_only) {
+        Ok(backend) => backend,
+        Err(e) => {
+            let e = error::TdError::from(e);
+            eprintln!("Error opening task storage: {}", e);
+            std::process::exit(e.exit_code());
+        }
+    };
+
+    // prompt-today opens the store itself, and only when its cached output is stale
+    if let Some(("prompt-today", sub)) = matches.subcommand() {
+        let result = cli::prompt_today(
+            backend,
+            sub.get_one::<String>("tag").map(|s| s.to_string()),
+            sub.get_one::<String>("project").map(|s| s.to_string()),
+            sub.get_one::<String>("format").map(|s| s.to_string()),
+            sub.get_one::<String>("task-format").map(|s| s.to_string()),
+            &config,
+        );
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            std::process::exit(e.exit_code());
+        }
+        return;
+    }
+
+    // doctor reads the stored data as is, without the checks a normal load applies
+    if let Some(("doctor", sub)) = matches.subcommand() {
+        if let Err(e) = doctor::doctor(backend, sub.get_flag("yes")) {
+            eprintln!("Error: {}", e);
+            std::process::exit(e.exit_code());
+        }
+        return;
+    }
+
+    let mut store = match task_store::Store::with_backend(backend, read_only) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("Error initializing task store: {}", e);
+            std::process::exit(e.exit_code());
+        }
+    };
+
+    // The archive policy is applied while the store is locked for a change anyway
+    if let (false, Some(after)) = (read_only, &config.archive_after) {
+        if let Err(e) = archive::auto_archive(&mut store, after, &config) {
+            eprintln!("Warning: could not archive completed tasks: {}", e);
+        }
+    }
+
+    let mut exit_code = 0;
     let result = match matches.subcommand() {
         Some(("add", sub)) => match sub.get_one::<String>("task") {
             Some(task) => cli::add(
+                &mut store,
                 task.to_string(),
-                sub.get_one::<String>("date").map(|s| s.to_string()),
+                cli::AddOptions {
+                    date: sub.get_one::<String>("date").map(|s| s.to_string()),
+                    tags: sub
+                        .get_many::<String>("tag")
+                        .map(|tags| tags.cloned().collect())
+                        .unwrap_or_default(),
+                    project: sub.get_one::<String>("project").map(|s| s.to_string()),
+                    priority: sub.get_one::<String>("priority").map(|s| s.to_string()),
+                    every: sub.get_one::<String>("every").map(|s| s.to_string()),
+                    parent: sub.get_one::<String>("parent").map(|s| s.to_string()),
+                    at: sub.get_one::<String>("at").map(|s| s.to_string()),
+                    duration: sub.get_one::<String>("for").map(|s| s.to_string()),
+                },
+                &config,
             ),
-            None => {
-                eprintln!("Error: task is required");
-                Ok(())
-            }
+            None => Err(error::TdError::invalid_input("task is required")),
         },
         Some(("list", sub)) => cli::list(
-            sub.get_one::<String>("date").map(|s| s.to_string()),
-            sub.get_flag("week"),
-            sub.get_flag("month"),
-            sub.get_flag("done"),
-            sub.get_flag("pending"),
-            sub.get_one::<String>("from_id")
-                .and_then(|s| s.parse::<usize>().ok()),
-            sub.get_one::<String>("to_id")
-                .and_then(|s| s.parse::<usize>().ok()),
-            sub.get_one::<String>("search").map(|s| s.to_lowercase()),
+            &store,
+            task_filter(sub),
             sub.get_flag("json"),
+            sub.get_flag("times"),
+            sub.get_flag("archived"),
+            &config,
         ),
-        Some(("done", sub)) => match sub.get_one::<String>("id") {
-            Some(id_str) => match id_str.parse::<usize>() {
-                Ok(id) => cli::mark_done(id),
-                Err(_) => {
-                    eprintln!("Error: id must be a valid number");
-                    Ok(())
-                }
-            },
-            None => {
-                eprintln!("Error: id is required");
-                Ok(())
+        Some(("count", sub)) => cli::count(&store, task_filter(sub), &config).map(|n| {
+            // So `td count --exit-code` works as a shell condition
+            if n == 0 && sub.get_flag("exit-code") {
+                exit_code = error::NO_MATCHES_EXIT_CODE;
             }
-        },
-        Some(("rm", sub)) => match sub.get_one::<String>("id") {
-            Some(id_str) => match id_str.parse::<usize>() {
-                Ok(id) => cli::remove(id),
-                Err(_) => {
-                    eprintln!("Error: id must be a valid number");
-                    Ok(())
-                }
-            },
-            None => {
-                eprintln!("Error: id is required");
-                Ok(())
+        }),
+        Some(("log", sub)) => cli::log(
+            &store,
+            sub.get_one::<String>("since").map(|s| s.to_string()),
+            sub.get_flag("json"),
+            &config,
+        ),
+        Some(("done", sub)) => selected_ids(&store, sub, true, &config)
+            .and_then(|ids| cli::mark_done_many(&mut store, &ids, sub.get_flag("cascade"))),
+        Some(("rm", sub)) => selected_ids(&store, sub, true, &config)
+            .and_then(|ids| cli::remove_many(&mut store, &ids, sub.get_flag("yes"))),
+        Some(("edit", sub)) => selected_ids(&store, sub, false, &config).and_then(|ids| {
+            cli::edit_many(
+                &mut store,
+                &ids,
+                cli::EditOptions {
+                    task: sub.get_one::<String>("task").map(|s| s.to_string()),
+                    date: sub.get_one::<String>("date").map(|s| s.to_string()),
+                    priority: sub.get_one::<String>("priority").map(|s| s.to_string()),
+                    every: sub.get_one::<String>("every").map(|s| s.to_string()),
+                    at: sub.get_one::<String>("at").map(|s| s.to_string()),
+                    duration: sub.get_one::<String>("for").map(|s| s.to_string()),
+                    tags: sub
+                        .get_many::<String>("tag")
+                        .map(|tags| tags.cloned().collect())
+                        .unwrap_or_default(),
+                    project: sub.get_one::<String>("project").map(|s| s.to_string()),
+                },
+                &config,
+            )
+        }),
+        Some(("review", sub)) => cli::review(
+            &store,
+            sub.get_one::<String>("tag").map(|s| s.to_string()),
+            sub.get_one::<String>("project").map(|s| s.to_string()),
+            &config,
+        ),
+        Some(("reuse", sub)) => selected_ids(&store, sub, false, &config).and_then(|ids| {
+            cli::reuse_many(
+                &mut store,
+                &ids,
+                sub.get_one::<String>("date").map(|s| s.to_string()),
+                &config,
+            )
+        }),
+        Some((name @ ("block" | "unblock"), sub)) => {
+            let on: Vec<String> = sub
+                .get_many::<String>("on")
+                .map(|ids| ids.cloned().collect())
+                .unwrap_or_default();
+            match (
+                selected_ids(&store, sub, false, &config),
+                cli::parse_id_args(&on),
+            ) {
+                (Ok(ids), Ok(on)) if name == "block" => cli::block(&mut store, &ids, &on),
+                (Ok(ids), Ok(on)) => cli::unblock(&mut store, &ids, &on),
+                (Err(e), _) | (_, Err(e)) => Err(e),
             }
-        },
-        Some(("edit", sub)) => match sub.get_one::<String>("id") {
+        }
+        Some(("note", sub)) => match sub.get_one::<String>("id") {
             Some(id_str) => match id_str.parse::<usize>() {
-                Ok(id) => cli::edit(
-                    id,
-                    sub.get_one::<String>("task").map(|s| s.to_string()),
-                    sub.get_one::<String>("date").map(|s| s.to_string()),
-                ),
-                Err(_) => {
-                    eprintln!("Error: id must be a valid number");
-                    Ok(())
-                }
+                Ok(id) => cli::note(&mut store, id),
+                Err(_) => Err(error::TdError::invalid_input("id must be a valid number")),
             },
-            None => {
-                eprintln!("Error: id is required");
-                Ok(())
-            }
+            None => Err(error::TdError::invalid_input("id is required")),
         },
-        Some(("prompt-today", _)) => cli::prompt_today(),
-        Some(("review", _)) => cli::review(),
-        Some(("reuse", sub)) => match sub.get_one::<String>("id") {
+        Some(("show", sub)) => match sub.get_one::<String>("id") {
             Some(id_str) => match id_str.parse::<usize>() {
-                Ok(id) => cli::reuse(id, sub.get_one::<String>("date").map(|s| s.to_string())),
-                Err(_) => {
-                    eprintln!("Error: id must be a valid number");
-                    Ok(())
-                }
+                Ok(id) => cli::show(&store, id, &config),
+                Err(_) => Err(error::TdError::invalid_input("id must be a valid number")),
             },
-            None => {
-                eprintln!("Error: id is required");
-                Ok(())
-            }
+            None => Err(error::TdError::invalid_input("id is required")),
         },
+        Some(("remind", sub)) => remind::remind(
+            &mut store,
+            // $TD_NOTIFY sets the notifier when --notify isn't given
+            &remind::Notifier::from_arg(
+                sub.get_one::<String>("notify")
+                    .cloned()
+                    .or_else(|| std::env::var("TD_NOTIFY").ok())
+                    .as_deref(),
+            ),
+            sub.get_one::<String>("before").map(|s| s.to_string()),
+            sub.get_flag("watch"),
+            *sub.get_one::<u64>("interval").unwrap_or(&30),
+        ),
+        Some(("archive", sub)) => archive::archive(
+            &mut store,
+            sub.get_one::<String>("before").map(|s| s.to_string()),
+            &config,
+        ),
+        Some(("undo", _)) => cli::undo(&mut store),
+        Some(("redo", _)) => cli::redo(&mut store),
         Some(("init", sub)) => {
             if let Some(shell) = sub.get_one::<String>("shell") {
                 shell::init_shell(shell);
                 Ok(())
             } else {
-                eprintln!("Error: shell is required");
-                Ok(())
+                Err(error::TdError::invalid_input("shell is required"))
             }
         }
         _ => {
@@ -222,13 +534,152 @@ fn main() {
         }
     };
 
-    if let Err(e) = task_store::TaskStore::save_to_disk() {
-        eprintln!("Error saving tasks to disk: {}", e);
-        std::process::exit(1);
+    // A command that fails partway saves nothing, so bulk changes apply to
+    // every task or to none
+    if !read_only && result.is_ok() {
+        let changes = match store.save() {
+            Ok(changes) => changes,
+            Err(e) => {
+                eprintln!("Error saving tasks to disk: {}", e);
+                std::process::exit(e.exit_code());
+            }
+        };
+
+        let history = match matches.subcommand_name() {
+            Some("undo") => cli::History::Undo,
+            Some("redo") => cli::History::Redo,
+            _ => cli::History::Record(std::env::args().skip(1).collect::<Vec<_>>().join(" ")),
+        };
+        if let Err(e) = cli::record_history(&store, history, changes) {
+            eprintln!("Warning: could not update undo history: {}", e);
+        }
     }
+    drop(store);
 
     if let Err(e) = result {
         eprintln!("Error: {}", e);
-        std::process::exit(1);
+        std::process::exit(e.exit_code());
+    }
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+}
+
+// Task selection flags shared by `list`, `count` and the filter-driven bulk commands
+fn filter_args() -> Vec<Arg> {
+    vec![
+        Arg::new("date")
+            .long("date")
+            .value_name("DATE")
+            .help("Tasks for a specific date (YYYY-MM-DD or e.g. fri, +3d)"),
+        Arg::new("from_date")
+            .long("from-date")
+            .value_name("DATE")
+            .help("Tasks dated on or after DATE"),
+        Arg::new("to_date")
+            .long("to-date")
+            .value_name("DATE")
+            .help("Tasks dated on or before DATE"),
+        Arg::new("week")
+            .long("week")
+            .value_name("YYYY-Www")
+            .num_args(0..=1)
+            .default_missing_value("this")
+            .help("Tasks for the current week, or a given ISO week"),
+        Arg::new("last_week")
+            .long("last-week")
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with_all(["week", "next_week"])
+            .help("Tasks for the previous week"),
+        Arg::new("next_week")
+            .long("next-week")
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with("week")
+            .help("Tasks for the following week"),
+        Arg::new("month")
+            .long("month")
+            .value_name("YYYY-MM")
+            .num_args(0..=1)
+            .default_missing_value("this")
+            .help("Tasks for the current month, or a given month"),
+        Arg::new("overdue")
+            .long("overdue")
+            .action(clap::ArgAction::SetTrue)
+            .help("Only unfinished tasks dated before today"),
+        Arg::new("done")
+            .long("done")
+            .action(clap::ArgAction::SetTrue)
+            .help("Only completed tasks"),
+        Arg::new("pending")
+            .long("pending")
+            .action(clap::ArgAction::SetTrue)
+            .help("Only pending tasks"),
+        Arg::new("from_id")
+            .long("from")
+            .value_name("ID")
+            .help("Tasks with ID greater than or equal to specified value"),
+        Arg::new("to_id")
+            .long("to")
+            .value_name("ID")
+            .help("Tasks with ID less than or equal to specified value"),
+        Arg::new("search")
+            .long("search")
+            .value_name("KEYWORD")
+            .help("Search tasks by keyword in task content"),
+        Arg::new("tag")
+            .long("tag")
+            .value_name("TAG")
+            .help("Only tasks with the given tag"),
+        Arg::new("project")
+            .long("project")
+            .value_name("PROJECT")
+            .help("Only tasks in the given project"),
+    ]
+}
+
+fn task_filter(sub: &ArgMatches) -> cli::TaskFilter {
+    cli::TaskFilter {
+        date: sub.get_one::<String>("date").map(|s| s.to_string()),
+        from_date: sub.get_one::<String>("from_date").map(|s| s.to_string()),
+        to_date: sub.get_one::<String>("to_date").map(|s| s.to_string()),
+        week: sub.get_one::<String>("week").map(|s| s.to_string()),
+        month: sub.get_one::<String>("month").map(|s| s.to_string()),
+        last_week: sub.get_flag("last_week"),
+        next_week: sub.get_flag("next_week"),
+        overdue: sub.get_flag("overdue"),
+        show_done: sub.get_flag("done"),
+        show_pending: sub.get_flag("pending"),
+        from_id: sub
+            .get_one::<String>("from_id")
+            .and_then(|s| s.parse::<usize>().ok()),
+        to_id: sub
+            .get_one::<String>("to_id")
+            .and_then(|s| s.parse::<usize>().ok()),
+        search: sub.get_one::<String>("search").map(|s| s.to_lowercase()),
+        tag: sub.get_one::<String>("tag").map(|s| s.to_string()),
+        project: sub.get_one::<String>("project").map(|s| s.to_string()),
+    }
+}
+
+// Task IDs from positional arguments (3 5 7, 10-20), or from filter flags when allowed
+fn selected_ids(
+    store: &task_store::Store,
+    sub: &ArgMatches,
+    filterable: bool,
+    config: &config::Config,
+) -> error::Result<Vec<usize>> {
+    let id_args: Vec<String> = sub
+        .get_many::<String>("id")
+        .map(|ids| ids.cloned().collect())
+        .unwrap_or_default();
+    if !id_args.is_empty() {
+        return cli::parse_id_args(&id_args);
+    }
+    if filterable {
+        let filter = task_filter(sub);
+        if filter != cli::TaskFilter::default() {
+            return cli::select_task_ids(store, &filter, config);
+        }
     }
+    Err(error::TdError::invalid_input("id is required"))
 }
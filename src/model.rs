@@ -1,13 +1,184 @@
+use crate::error::TdError;
 use anyhow::Result;
-use chrono::Local;
+use chrono::{Datelike, Local, Months, NaiveDate, SecondsFormat, Weekday};
 use fs2::FileExt;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::fs;
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// How long a command waits for another td process to release the store
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Version of the todo.json layout this build writes. Older files are upgraded
+// through MIGRATIONS; newer ones are refused rather than misread.
+pub const SCHEMA_VERSION: u64 = 1;
+
+// MIGRATIONS[n] turns the task list of a version-n file into version n + 1,
+// working on raw JSON so fields can be renamed or reshaped before parsing.
+// Version 0 is the original bare array of tasks.
+const MIGRATIONS: [fn(Value) -> Result<Value>; SCHEMA_VERSION as usize] = [
+    // 1: the array moved into a {"version", "tasks"} envelope; tasks are unchanged
+    |tasks| Ok(tasks),
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+}
+
+impl Priority {
+    pub fn label(self) -> &'static str {
+        match self {
+            Priority::High => "H",
+            Priority::Medium => "M",
+            Priority::Low => "L",
+        }
+    }
+}
+
+impl std::str::FromStr for Priority {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "h" | "high" | "1" => Ok(Priority::High),
+            "m" | "medium" | "2" => Ok(Priority::Medium),
+            "l" | "low" | "3" => Ok(Priority::Low),
+            _ => Err(anyhow::anyhow!(
+                "Invalid priority '{}'. Use H/M/L or 1-3.",
+                s
+            )),
+        }
+    }
+}
+
+// Recurrence rule, stored in the JSON as its textual form (e.g. "2w", "mon,thu")
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub enum Recurrence {
+    Days(u32),
+    Weekdays,
+    Weeks(u32),
+    Months(u32),
+    OnDays(Vec<Weekday>),
+}
+
+impl Recurrence {
+    // First occurrence strictly after `date`; `None` if it would fall past
+    // the last date chrono can represent
+    pub fn next_after(&self, date: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Recurrence::Days(n) => date.checked_add_signed(chrono::Duration::try_days(*n as i64)?),
+            Recurrence::Weeks(n) => {
+                date.checked_add_signed(chrono::Duration::try_weeks(*n as i64)?)
+            }
+            Recurrence::Months(n) => date.checked_add_months(Months::new(*n)),
+            Recurrence::Weekdays => {
+                let mut next = date.succ_opt()?;
+                while matches!(next.weekday(), Weekday::Sat | Weekday::Sun) {
+                    next = next.succ_opt()?;
+                }
+                Some(next)
+            }
+            Recurrence::OnDays(days) => {
+                let mut next = date.succ_opt()?;
+                while !days.contains(&next.weekday()) {
+                    next = next.succ_opt()?;
+                }
+                Some(next)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Recurrence {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let rule = s.trim().to_lowercase();
+        let invalid = || {
+            anyhow::anyhow!(
+                "Invalid recurrence '{}'. Use day, weekday, week, month, 2w, 3d or mon,thu.",
+                s
+            )
+        };
+
+        match rule.as_str() {
+            "day" | "daily" => return Ok(Recurrence::Days(1)),
+            "weekday" | "weekdays" => return Ok(Recurrence::Weekdays),
+            "week" | "weekly" => return Ok(Recurrence::Weeks(1)),
+            "month" | "monthly" => return Ok(Recurrence::Months(1)),
+            _ => {}
+        }
+
+        if rule.starts_with(|c: char| c.is_ascii_digit()) {
+            let unit_at = rule.len() - rule.chars().last().map_or(0, char::len_utf8);
+            let (num, unit) = rule.split_at(unit_at);
+            let n: u32 = num.parse().map_err(|_| invalid())?;
+            if n == 0 {
+                return Err(invalid());
+            }
+            return match unit {
+                "d" => Ok(Recurrence::Days(n)),
+                "w" => Ok(Recurrence::Weeks(n)),
+                "m" => Ok(Recurrence::Months(n)),
+                _ => Err(invalid()),
+            };
+        }
+
+        let mut days = Vec::new();
+        for name in rule.split(',') {
+            let day: Weekday = name.trim().parse().map_err(|_| invalid())?;
+            if !days.contains(&day) {
+                days.push(day);
+            }
+        }
+        days.sort_by_key(|d| d.num_days_from_monday());
+        Ok(Recurrence::OnDays(days))
+    }
+}
+
+impl std::fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Recurrence::Days(1) => write!(f, "day"),
+            Recurrence::Days(n) => write!(f, "{}d", n),
+            Recurrence::Weekdays => write!(f, "weekday"),
+            Recurrence::Weeks(1) => write!(f, "week"),
+            Recurrence::Weeks(n) => write!(f, "{}w", n),
+            Recurrence::Months(1) => write!(f, "month"),
+            Recurrence::Months(n) => write!(f, "{}m", n),
+            Recurrence::OnDays(days) => {
+                let names: Vec<String> =
+                    days.iter().map(|d| d.to_string().to_lowercase()).collect();
+                write!(f, "{}", names.join(","))
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Recurrence> for String {
+    fn from(r: Recurrence) -> Self {
+        r.to_string()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct Task {
     pub id: usize,
     pub task: String,
@@ -15,61 +186,294 @@ pub struct Task {
     pub done: bool,
     #[serde(default)]
     pub reuse_by: Option<usize>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    // RFC3339 timestamps with the local offset
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub completed_at: Option<String>,
+    // Free-form, possibly multi-line context such as links or repro steps
+    #[serde(default)]
+    pub notes: Option<String>,
+    // ID of the task this one is a subtask of
+    #[serde(default)]
+    pub parent: Option<usize>,
+    // IDs of tasks that must be finished before this one
+    #[serde(default)]
+    pub blocked_by: Vec<usize>,
+    // Optional time of day ("HH:MM") the task is due on its date, and how long it takes
+    #[serde(default)]
+    pub due_time: Option<String>,
+    #[serde(default)]
+    pub duration: Option<u32>,
 }
 
-pub fn get_storage_path() -> Result<PathBuf> {
+impl Task {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    pub fn in_project(&self, project: &str) -> bool {
+        self.project
+            .as_deref()
+            .is_some_and(|p| p.eq_ignore_ascii_case(project))
+    }
+
+    // Blockers that still exist and aren't done yet
+    pub fn open_blockers(&self, tasks: &[Task]) -> Vec<usize> {
+        self.blocked_by
+            .iter()
+            .copied()
+            .filter(|id| tasks.iter().any(|t| t.id == *id && !t.done))
+            .collect()
+    }
+
+    // Listing order: by date, timed tasks first in chronological order,
+    // then by priority and ID
+    pub fn schedule_key(&self) -> (&str, bool, Option<&str>, u8, usize) {
+        (
+            &self.date,
+            self.due_time.is_none(),
+            self.due_time.as_deref(),
+            self.priority_rank(),
+            self.id,
+        )
+    }
+
+    // Sort rank within a date: high first, tasks without a priority last
+    pub fn priority_rank(&self) -> u8 {
+        match self.priority {
+            Some(Priority::High) => 0,
+            Some(Priority::Medium) => 1,
+            Some(Priority::Low) => 2,
+            None => 3,
+        }
+    }
+}
+
+// Directory holding config.toml and, by default, the task store
+pub fn get_config_dir() -> Result<PathBuf> {
     // Check for a test-specific environment variable first
     if let Ok(test_path) = std::env::var("TD_TEST_CONFIG_DIR") {
-        let mut path = PathBuf::from(test_path);
-        path.push("td-rs/todo.json");
-        return Ok(path);
+        return Ok(PathBuf::from(test_path).join("td-rs"));
     }
 
-    let mut path =
+    let path =
         dirs::config_dir().ok_or_else(|| anyhow::Error::msg("Failed to get config directory"))?;
-    path.push("td-rs/todo.json");
-    Ok(path)
+    Ok(path.join("td-rs"))
+}
+
+// Default location of the task store; config.toml's `storage_path` overrides it
+pub fn get_storage_path() -> Result<PathBuf> {
+    Ok(get_config_dir()?.join("todo.json"))
 }
 
 pub fn today_str() -> String {
     Local::now().format("%Y-%m-%d").to_string()
 }
 
-pub fn load_tasks() -> Result<Vec<Task>> {
-    let path = get_storage_path()?;
+pub fn now_str() -> String {
+    Local::now().to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+#[derive(Serialize)]
+struct TaskFile<'a> {
+    version: u64,
+    tasks: &'a [Task],
+}
+
+pub fn load_tasks(path: &Path) -> Result<Vec<Task>> {
+    Ok(read_task_file(path)?.0)
+}
+
+// The tasks in the todo.json at `path`, upgraded to SCHEMA_VERSION, and the
+// version the file was written in
+fn read_task_file(path: &Path) -> Result<(Vec<Task>, u64)> {
     if !path.exists() {
-        return Ok(Vec::new());
+        return Ok((Vec::new(), SCHEMA_VERSION));
+    }
+
+    // Writers replace the file with a rename, so a plain read always sees a complete version
+    let data = fs::read_to_string(path)?;
+    let corrupt = |reason: String| TdError::Corrupt {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let (version, mut tasks) = match serde_json::from_str(&data) {
+        Ok(Value::Array(tasks)) => (0, Value::Array(tasks)),
+        Ok(Value::Object(mut file)) => {
+            let version = file
+                .get("version")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| corrupt("missing format version".to_string()))?;
+            let tasks = file
+                .remove("tasks")
+                .ok_or_else(|| corrupt("missing task list".to_string()))?;
+            (version, tasks)
+        }
+        Ok(_) => return Err(corrupt("expected a task list".to_string()).into()),
+        Err(e) => return Err(corrupt(e.to_string()).into()),
+    };
+    if version > SCHEMA_VERSION {
+        return Err(TdError::UnsupportedVersion {
+            path: path.to_path_buf(),
+            version,
+        }
+        .into());
+    }
+
+    for migrate in &MIGRATIONS[version as usize..] {
+        tasks = migrate(tasks).map_err(|e| corrupt(e.to_string()))?;
     }
+    let tasks = serde_json::from_value(tasks).map_err(|e| corrupt(e.to_string()))?;
+    Ok((tasks, version))
+}
 
-    let mut file = File::open(&path)?;
-    file.lock_shared()?;
-    let mut data = String::new();
-    file.read_to_string(&mut data)?;
-    file.unlock()?;
+pub fn save_tasks(path: &Path, tasks: &[Task]) -> Result<()> {
+    let json = serde_json::to_string_pretty(&TaskFile {
+        version: SCHEMA_VERSION,
+        tasks,
+    })?;
+    write_atomic(path, json.as_bytes())
+}
 
-    let tasks = serde_json::from_str(&data)?;
-    Ok(tasks)
+// The tasks that could still be read from a damaged todo.json or todo.db
+pub struct Salvage {
+    pub tasks: Vec<Task>,
+    // Task entries in the file that couldn't be recovered
+    pub lost: usize,
 }
 
-pub fn save_tasks(tasks: &[Task]) -> Result<()> {
-    let path = get_storage_path()?;
+// Recover what is left of a todo.json that doesn't parse by reading each
+// JSON object in it on its own. A damaged file's version can't be trusted,
+// so the objects are read as current-format tasks.
+pub fn salvage_task_file(path: &Path) -> Result<Salvage> {
+    let data = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+    let mut tasks = Vec::new();
+    let mut resume_at = 0;
+    for (start, _) in data.match_indices('{') {
+        if start < resume_at {
+            continue;
+        }
+        let mut values = serde_json::Deserializer::from_str(&data[start..]).into_iter::<Value>();
+        let Some(Ok(value)) = values.next() else {
+            continue;
+        };
+        // Anything without a "task" key is the envelope, so look inside it
+        if value.get("task").is_none() {
+            continue;
+        }
+        if let Ok(task) = serde_json::from_value(value) {
+            tasks.push(task);
+        }
+        resume_at = start + values.byte_offset();
+    }
+    // Inside strings the quotes are escaped, so this only counts keys
+    let entries = data.matches("\"task\":").count();
+    Ok(Salvage {
+        lost: entries.saturating_sub(tasks.len()),
+        tasks,
+    })
+}
+
+// Rewrite a todo.json from an older version in the current format, keeping
+// the original as `todo.json.v<N>.bak`. Returns the backup's path if the
+// file was upgraded.
+pub fn upgrade_task_file(path: &Path) -> Result<Option<PathBuf>> {
+    let (tasks, version) = read_task_file(path)?;
+    if version == SCHEMA_VERSION {
+        return Ok(None);
+    }
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let backup = path.with_file_name(format!("{}.v{}.bak", file_name, version));
+    fs::copy(path, &backup)?;
+    save_tasks(path, &tasks)?;
+    Ok(Some(backup))
+}
+
+// Write `data` to a temp file next to `path`, fsync it and rename it over `path`,
+// so readers and crashes only ever see the old or the new contents
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("Invalid storage path: {:?}", path))?;
+    fs::create_dir_all(dir)?;
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp_path = dir.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let written = File::create(&tmp_path).and_then(|mut file| {
+        file.write_all(data)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp_path, path)) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e.into());
+    }
+
+    // Persist the rename itself; not every platform can open a directory for syncing
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+// Exclusive lock on the task store, released when dropped
+pub struct StoreLock {
+    file: File,
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+// todo.json.lock for todo.json
+pub fn get_lock_path(store_path: &Path) -> PathBuf {
+    let name = store_path.file_name().unwrap_or_default().to_string_lossy();
+    store_path.with_file_name(format!("{}.lock", name))
+}
+
+// Take the lock on the store at `store_path`, waiting briefly for other td
+// processes to finish. The lock lives in a separate file because saving
+// replaces todo.json itself.
+pub fn lock_storage(store_path: &Path) -> Result<StoreLock> {
+    let path = get_lock_path(store_path);
     if let Some(dir) = path.parent() {
         fs::create_dir_all(dir)?;
     }
-    let json = serde_json::to_string_pretty(tasks)?;
-    let mut file = File::create(&path)?;
-    file.lock_exclusive()?;
-    file.write_all(json.as_bytes())?;
-    file.flush()?;
-    file.unlock()?;
-    Ok(())
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)?;
+
+    let started = Instant::now();
+    loop {
+        match file.try_lock_exclusive() {
+            Ok(()) => return Ok(StoreLock { file }),
+            Err(_) if started.elapsed() < LOCK_TIMEOUT => {
+                std::thread::sleep(Duration::from_millis(20))
+            }
+            Err(_) => return Err(TdError::StoreLocked(path).into()),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
-    use std::path::PathBuf;
     use tempfile::TempDir;
 
     #[test]
@@ -89,6 +493,106 @@ mod tests {
         assert!(today.chars().nth(7) == Some('-'));
     }
 
+    #[test]
+    fn test_parse_priority() {
+        assert_eq!("H".parse::<Priority>().unwrap(), Priority::High);
+        assert_eq!("medium".parse::<Priority>().unwrap(), Priority::Medium);
+        assert_eq!("3".parse::<Priority>().unwrap(), Priority::Low);
+        assert!("urgent".parse::<Priority>().is_err());
+    }
+
+    #[test]
+    fn test_recurrence_rules() {
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        // 2025-08-01 is a Friday
+        let friday = date("2025-08-01");
+
+        let daily: Recurrence = "day".parse().unwrap();
+        assert_eq!(daily.next_after(friday), Some(date("2025-08-02")));
+
+        let weekday: Recurrence = "weekday".parse().unwrap();
+        assert_eq!(weekday.next_after(friday), Some(date("2025-08-04")));
+
+        let fortnightly: Recurrence = "2w".parse().unwrap();
+        assert_eq!(fortnightly.next_after(friday), Some(date("2025-08-15")));
+        assert_eq!(fortnightly.to_string(), "2w");
+
+        let monthly: Recurrence = "month".parse().unwrap();
+        assert_eq!(
+            monthly.next_after(date("2025-01-31")),
+            Some(date("2025-02-28"))
+        );
+
+        let on_days: Recurrence = "Thu, mon".parse().unwrap();
+        assert_eq!(on_days.to_string(), "mon,thu");
+        assert_eq!(on_days.next_after(friday), Some(date("2025-08-04")));
+        assert_eq!(
+            on_days.next_after(date("2025-08-04")),
+            Some(date("2025-08-07"))
+        );
+
+        // Past the end of the calendar there is no next occurrence
+        let far: Recurrence = "99999999d".parse().unwrap();
+        assert_eq!(far.next_after(friday), None);
+        assert_eq!(weekday.next_after(NaiveDate::MAX), None);
+
+        assert!("0d".parse::<Recurrence>().is_err());
+        assert!("fortnight".parse::<Recurrence>().is_err());
+    }
+
+    #[test]
+    fn test_task_file_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("todo.json");
+
+        // Files from before versioning are a bare array
+        fs::write(
+            &path,
+            r#"[{"id": 1, "task": "Old", "date": "2023-01-01", "done": false}]"#,
+        )
+        .unwrap();
+        assert_eq!(load_tasks(&path).unwrap()[0].task, "Old");
+
+        let backup = upgrade_task_file(&path).unwrap().unwrap();
+        assert!(backup.ends_with("todo.json.v0.bak"));
+        assert!(fs::read_to_string(&backup).unwrap().starts_with('['));
+        let data: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(data["version"], SCHEMA_VERSION);
+        assert_eq!(data["tasks"][0]["task"], "Old");
+        // Already current, so nothing to do
+        assert!(upgrade_task_file(&path).unwrap().is_none());
+
+        fs::write(&path, r#"{"version": 99, "tasks": []}"#).unwrap();
+        let err = TdError::from(load_tasks(&path).unwrap_err());
+        assert!(matches!(
+            err,
+            TdError::UnsupportedVersion { version: 99, .. }
+        ));
+    }
+
+    #[test]
+    fn test_salvage_task_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("todo.json");
+        let task = |id: usize| Task {
+            id,
+            task: format!("Task {} with \"task\": {{ inside", id),
+            date: "2023-01-01".to_string(),
+            ..Default::default()
+        };
+        save_tasks(&path, &[task(1), task(2), task(3)]).unwrap();
+
+        // Cut the file off in the middle of the last task, and break the second
+        let data = fs::read_to_string(&path).unwrap();
+        let data = data[..data.len() - 40].replacen("\"id\": 2", "\"id\": \"two\"", 1);
+        fs::write(&path, data).unwrap();
+        assert!(load_tasks(&path).is_err());
+
+        let salvage = salvage_task_file(&path).unwrap();
+        assert_eq!(salvage.tasks, vec![task(1)]);
+        assert_eq!(salvage.lost, 2);
+    }
+
     #[test]
     fn test_save_and_load_tasks() {
         // Create a temporary directory for testing
@@ -105,6 +609,7 @@ mod tests {
                 date: "2023-01-01".to_string(),
                 done: false,
                 reuse_by: None,
+                ..Default::default()
             },
             Task {
                 id: 2,
@@ -112,47 +617,41 @@ mod tests {
                 date: "2023-01-02".to_string(),
                 done: true,
                 reuse_by: Some(1),
+                ..Default::default()
             },
         ];
 
-        // Save tasks with our helper function that uses the temp path
-        let result = save_tasks_with_path(&tasks, &storage_path);
+        let result = save_tasks(&storage_path, &tasks);
         assert!(result.is_ok());
 
-        // Load tasks from the temp path
-        let loaded_tasks = load_tasks_from_path(&storage_path).unwrap();
+        let loaded_tasks = load_tasks(&storage_path).unwrap();
         assert_eq!(loaded_tasks.len(), 2);
         assert_eq!(loaded_tasks[0].id, 1);
         assert_eq!(loaded_tasks[0].task, "Test task");
         assert_eq!(loaded_tasks[0].date, "2023-01-01");
-        assert_eq!(loaded_tasks[0].done, false);
+        assert!(!loaded_tasks[0].done);
         assert_eq!(loaded_tasks[0].reuse_by, None);
         assert_eq!(loaded_tasks[1].id, 2);
         assert_eq!(loaded_tasks[1].task, "Another test task");
         assert_eq!(loaded_tasks[1].date, "2023-01-02");
-        assert_eq!(loaded_tasks[1].done, true);
+        assert!(loaded_tasks[1].done);
         assert_eq!(loaded_tasks[1].reuse_by, Some(1));
     }
 
-    // Helper function for testing, using a specified path instead of default
-    fn save_tasks_with_path(tasks: &[Task], path: &PathBuf) -> Result<()> {
-        if let Some(dir) = path.parent() {
-            fs::create_dir_all(dir)?;
-        }
-        let json = serde_json::to_string_pretty(tasks)?;
-        let mut file = fs::File::create(&path)?;
-        file.write_all(json.as_bytes())?;
-        file.flush()?;
-        Ok(())
-    }
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("td-rs").join("todo.json");
 
-    // Helper function for testing, using a specified path instead of default
-    fn load_tasks_from_path(path: &PathBuf) -> Result<Vec<Task>> {
-        if !path.exists() {
-            return Ok(Vec::new());
-        }
-        let data = fs::read_to_string(path)?;
-        let tasks = serde_json::from_str(&data)?;
-        Ok(tasks)
+        write_atomic(&path, b"[1]").unwrap();
+        write_atomic(&path, b"[1, 2]").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[1, 2]");
+
+        // Only the target file is left behind, no temp files
+        let entries: Vec<_> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec![std::ffi::OsString::from("todo.json")]);
     }
 }
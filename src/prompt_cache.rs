@@ -0,0 +1,96 @@
+use crate::model::write_atomic;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const EXPIRY_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+// What the cached output was computed from: the task file as it was on disk,
+// the day, and everything else that shapes the output (arguments, config, colour)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CacheKey {
+    store_modified: Option<String>,
+    store_len: u64,
+    date: String,
+    params: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CacheEntry {
+    key: CacheKey,
+    output: String,
+    // Due times make the output change during the day, e.g. when a task turns urgent
+    expires_at: Option<String>,
+}
+
+// Kept next to the store's data file (todo.json or todo.db)
+pub fn get_prompt_cache_path(data_path: &Path) -> PathBuf {
+    data_path.with_file_name("prompt_cache.json")
+}
+
+// Key for the current state of the store at `data_path`; `params` is anything
+// else the output depends on
+pub fn cache_key(data_path: &Path, params: &str, now: NaiveDateTime) -> Result<CacheKey> {
+    let (store_modified, store_len) = match fs::metadata(data_path) {
+        Ok(meta) => {
+            let modified = meta.modified()?.duration_since(UNIX_EPOCH)?;
+            (
+                Some(format!(
+                    "{}.{:09}",
+                    modified.as_secs(),
+                    modified.subsec_nanos()
+                )),
+                meta.len(),
+            )
+        }
+        Err(_) => (None, 0),
+    };
+    let mut hasher = DefaultHasher::new();
+    params.hash(&mut hasher);
+    colored::control::SHOULD_COLORIZE
+        .should_colorize()
+        .hash(&mut hasher);
+
+    Ok(CacheKey {
+        store_modified,
+        store_len,
+        date: now.date().format("%Y-%m-%d").to_string(),
+        params: hasher.finish(),
+    })
+}
+
+// The cached output, if it was computed for `key` and hasn't expired
+pub fn lookup(data_path: &Path, key: &CacheKey, now: NaiveDateTime) -> Option<String> {
+    let data = fs::read_to_string(get_prompt_cache_path(data_path)).ok()?;
+    let entry: CacheEntry = serde_json::from_str(&data).ok()?;
+    if entry.key != *key {
+        return None;
+    }
+    match entry.expires_at {
+        Some(expires) if NaiveDateTime::parse_from_str(&expires, EXPIRY_FORMAT).ok()? <= now => {
+            None
+        }
+        _ => Some(entry.output),
+    }
+}
+
+pub fn store(
+    data_path: &Path,
+    key: &CacheKey,
+    output: &str,
+    expires_at: Option<NaiveDateTime>,
+) -> Result<()> {
+    let entry = CacheEntry {
+        key: key.clone(),
+        output: output.to_string(),
+        expires_at: expires_at.map(|t| t.format(EXPIRY_FORMAT).to_string()),
+    };
+    write_atomic(
+        &get_prompt_cache_path(data_path),
+        serde_json::to_string(&entry)?.as_bytes(),
+    )
+}
@@ -0,0 +1,228 @@
+use crate::cli::{due_status, format_time_slot, minutes_until_due};
+use crate::dates::parse_duration_minutes;
+use crate::error::{Result, TdError};
+use crate::model::{now_str, write_atomic, Task};
+use crate::task_store::Store;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+// Tasks that are already this late when first seen still get one reminder
+const LATE_WINDOW_MINUTES: i64 = 60;
+
+// Where reminders are delivered
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notifier {
+    // Print to stdout, for headless use or piping
+    Stdout,
+    // Run a command with the title and body as its last two arguments,
+    // e.g. `notify-send` or a custom script
+    Command(String),
+}
+
+impl Notifier {
+    pub fn from_arg(arg: Option<&str>) -> Notifier {
+        match arg.map(str::trim) {
+            None | Some("") | Some("stdout") => Notifier::Stdout,
+            Some(cmd) => Notifier::Command(cmd.to_string()),
+        }
+    }
+
+    fn notify(&self, task: &Task, title: &str, body: &str) -> Result<()> {
+        match self {
+            Notifier::Stdout => {
+                println!("[⏰] {}: {}", title, body);
+                Ok(())
+            }
+            Notifier::Command(cmd) => {
+                // Go through the shell so commands with arguments work
+                #[cfg(unix)]
+                let mut command = {
+                    let mut c = std::process::Command::new("sh");
+                    c.arg("-c").arg(format!("{} \"$@\"", cmd)).arg(cmd);
+                    c
+                };
+                #[cfg(not(unix))]
+                let mut command = std::process::Command::new(cmd);
+
+                let status = command
+                    .arg(title)
+                    .arg(body)
+                    .env("TD_TASK_ID", task.id.to_string())
+                    .env("TD_TASK", &task.task)
+                    .env("TD_DUE", task.due_time.as_deref().unwrap_or(""))
+                    .status()
+                    .map_err(|e| {
+                        TdError::Other(anyhow::anyhow!("Could not run notifier '{}': {}", cmd, e))
+                    })?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(TdError::Other(anyhow::anyhow!(
+                        "Notifier '{}' exited with {}",
+                        cmd,
+                        status
+                    )))
+                }
+            }
+        }
+    }
+}
+
+pub fn get_reminders_path(store: &Store) -> PathBuf {
+    store.sibling_path("reminders.json")
+}
+
+// Reminders already sent, keyed by task ID, date and due time so that
+// rescheduling a task makes it eligible again
+fn load_sent(store: &Store) -> HashMap<String, String> {
+    fs::read_to_string(get_reminders_path(store))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_sent(store: &Store, sent: &HashMap<String, String>) -> Result<()> {
+    let json = serde_json::to_string_pretty(sent)?;
+    Ok(write_atomic(&get_reminders_path(store), json.as_bytes())?)
+}
+
+fn reminder_key(t: &Task) -> String {
+    format!(
+        "{}@{} {}",
+        t.id,
+        t.date,
+        t.due_time.as_deref().unwrap_or("")
+    )
+}
+
+// Pending, unblocked tasks due within `lead` minutes (or recently missed)
+// that haven't been reminded about yet
+fn due_reminders<'a>(
+    tasks: &'a [Task],
+    sent: &HashMap<String, String>,
+    lead: i64,
+    now: chrono::NaiveDateTime,
+) -> Vec<&'a Task> {
+    let mut due: Vec<&Task> = tasks
+        .iter()
+        .filter(|t| t.open_blockers(tasks).is_empty())
+        .filter(|t| {
+            minutes_until_due(t, now).is_some_and(|m| m <= lead && m > -LATE_WINDOW_MINUTES)
+        })
+        .filter(|t| !sent.contains_key(&reminder_key(t)))
+        .collect();
+    due.sort_by_key(|t| t.schedule_key());
+    due
+}
+
+// Check once for tasks coming due, or keep checking every `interval` seconds
+pub fn remind(
+    store: &mut Store,
+    notifier: &Notifier,
+    before: Option<String>,
+    watch: bool,
+    interval: u64,
+) -> Result<()> {
+    let lead = before
+        .as_deref()
+        .map(parse_duration_minutes)
+        .transpose()?
+        .unwrap_or(10) as i64;
+
+    loop {
+        let now = chrono::Local::now().naive_local();
+        let mut sent = load_sent(store);
+        // Forget reminders for past days so the file doesn't grow forever
+        let today = now.date().format("%Y-%m-%d").to_string();
+        sent.retain(|key, _| {
+            key.split_once('@')
+                .and_then(|(_, rest)| rest.get(..10))
+                .is_some_and(|date| date >= today.as_str())
+        });
+
+        let due: Vec<Task> = due_reminders(store.tasks(), &sent, lead, now)
+            .into_iter()
+            .cloned()
+            .collect();
+        for t in &due {
+            let title = format!("td #{} {}", t.id, t.task);
+            let body = format!(
+                "{} ({})",
+                due_status(t, now).unwrap_or_default(),
+                format_time_slot(t).unwrap_or_default()
+            );
+            match notifier.notify(t, &title, &body) {
+                Ok(()) => {
+                    sent.insert(reminder_key(t), now_str());
+                }
+                Err(e) => eprintln!("Warning: {}", e),
+            }
+        }
+        save_sent(store, &sent)?;
+
+        if !watch {
+            return Ok(());
+        }
+        std::thread::sleep(std::time::Duration::from_secs(interval.max(1)));
+        // Pick up tasks added or changed by other td commands
+        store.reload()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_due_reminders() {
+        let now = NaiveDate::parse_from_str("2025-08-06", "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(14, 0, 0)
+            .unwrap();
+        let task = |id: usize, due_time: Option<&str>| Task {
+            id,
+            task: format!("Task {}", id),
+            date: "2025-08-06".to_string(),
+            due_time: due_time.map(str::to_string),
+            ..Default::default()
+        };
+        let mut done = task(5, Some("14:05"));
+        done.done = true;
+        let mut blocked = task(6, Some("14:05"));
+        blocked.blocked_by = vec![1];
+        let tasks = vec![
+            task(1, Some("14:08")),
+            task(2, Some("14:30")),
+            task(3, Some("12:30")),
+            task(4, None),
+            done,
+            blocked,
+            task(7, Some("13:30")),
+        ];
+
+        let ids = |sent: &HashMap<String, String>| -> Vec<usize> {
+            due_reminders(&tasks, sent, 10, now)
+                .iter()
+                .map(|t| t.id)
+                .collect()
+        };
+        // Due within 10 minutes, or missed within the last hour
+        assert_eq!(ids(&HashMap::new()), vec![7, 1]);
+
+        // Already-sent reminders aren't repeated
+        let sent = HashMap::from([(reminder_key(&tasks[0]), String::new())]);
+        assert_eq!(ids(&sent), vec![7]);
+    }
+
+    #[test]
+    fn test_notifier_from_arg() {
+        assert_eq!(Notifier::from_arg(None), Notifier::Stdout);
+        assert_eq!(Notifier::from_arg(Some("stdout")), Notifier::Stdout);
+        assert_eq!(
+            Notifier::from_arg(Some("notify-send -u critical")),
+            Notifier::Command("notify-send -u critical".to_string())
+        );
+    }
+}
@@ -0,0 +1,429 @@
+use crate::journal::Change;
+use crate::model::{load_tasks, salvage_task_file, save_tasks, upgrade_task_file, Salvage, Task};
+use anyhow::Result;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+// Where a `Store` keeps tasks between commands
+pub trait StorageBackend {
+    fn load(&self) -> Result<Vec<Task>>;
+
+    // Replace everything stored with `tasks`
+    fn save(&self, tasks: &[Task]) -> Result<()>;
+
+    // Persist one command's work: `tasks` is the full new list and `changes`
+    // what differs from the loaded one. Backends that can update single tasks
+    // apply just the changes, all or nothing; the default rewrites everything.
+    fn apply(&self, tasks: &[Task], changes: &[Change]) -> Result<()> {
+        let _ = changes;
+        self.save(tasks)
+    }
+
+    // Bring data written by an older td up to the current format; returns
+    // where the previous version was kept if anything changed
+    fn upgrade(&self) -> Result<Option<PathBuf>> {
+        Ok(None)
+    }
+
+    // What can still be read when `load` reports damage, for `td doctor`;
+    // `None` if this backend has no way to recover tasks
+    fn salvage(&self) -> Result<Option<Salvage>> {
+        Ok(None)
+    }
+
+    // The file holding the data, e.g. for change detection and backups
+    fn path(&self) -> &Path;
+}
+
+// Selected with `storage_backend` in config.toml
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    Json,
+    Sqlite,
+}
+
+// The backend for a store at `path` (todo.json); SQLite uses todo.db next to
+// it. `read_only` callers never write, so they skip creating anything.
+pub fn open_backend(
+    kind: BackendKind,
+    path: &Path,
+    read_only: bool,
+) -> Result<Box<dyn StorageBackend>> {
+    match kind {
+        BackendKind::Json => Ok(Box::new(JsonBackend::new(path.to_path_buf()))),
+        #[cfg(feature = "sqlite")]
+        BackendKind::Sqlite if read_only => Ok(Box::new(SqliteBackend::open_read_only(
+            path.with_extension("db"),
+            path,
+        )?)),
+        #[cfg(feature = "sqlite")]
+        BackendKind::Sqlite => Ok(Box::new(SqliteBackend::open(
+            path.with_extension("db"),
+            path,
+        )?)),
+        #[cfg(not(feature = "sqlite"))]
+        BackendKind::Sqlite => {
+            let _ = read_only;
+            Err(anyhow::anyhow!(
+                "td was built without SQLite support; rebuild with `--features sqlite`"
+            ))
+        }
+    }
+}
+
+// All tasks as one JSON array in todo.json
+pub struct JsonBackend {
+    path: PathBuf,
+}
+
+impl JsonBackend {
+    pub fn new(path: PathBuf) -> Self {
+        JsonBackend { path }
+    }
+}
+
+impl StorageBackend for JsonBackend {
+    fn load(&self) -> Result<Vec<Task>> {
+        load_tasks(&self.path)
+    }
+
+    fn save(&self, tasks: &[Task]) -> Result<()> {
+        save_tasks(&self.path, tasks)
+    }
+
+    fn upgrade(&self) -> Result<Option<PathBuf>> {
+        upgrade_task_file(&self.path)
+    }
+
+    fn salvage(&self) -> Result<Option<Salvage>> {
+        Ok(Some(salvage_task_file(&self.path)?))
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteBackend;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::*;
+    use crate::error::TdError;
+    use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+
+    // One row per task. The task itself is stored as JSON so new fields don't
+    // need schema changes; date and done are columns for indexed lookups.
+    // Until the first write creates the table, tasks are read from the JSON
+    // file at `import_from`.
+    pub struct SqliteBackend {
+        path: PathBuf,
+        import_from: PathBuf,
+        conn: Connection,
+    }
+
+    impl SqliteBackend {
+        // Open (or create) the database at `path`. A new database starts out
+        // with the tasks from the JSON file at `import_from`, if there is one;
+        // they are copied over by the first write.
+        pub fn open(path: PathBuf, import_from: &Path) -> Result<Self> {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let conn = Connection::open(&path)?;
+            let backend = Self::connect(path, import_from, conn)?;
+            backend.has_table()?;
+            Ok(backend)
+        }
+
+        // Open the database without write access. One that doesn't exist yet
+        // isn't created; an empty in-memory one stands in for it, so the
+        // tasks come from `import_from`.
+        pub fn open_read_only(path: PathBuf, import_from: &Path) -> Result<Self> {
+            let conn = if path.exists() {
+                Connection::open_with_flags(
+                    &path,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )?
+            } else {
+                Connection::open_in_memory()?
+            };
+            let backend = Self::connect(path, import_from, conn)?;
+            backend.has_table()?;
+            Ok(backend)
+        }
+
+        fn connect(path: PathBuf, import_from: &Path, conn: Connection) -> Result<Self> {
+            conn.busy_timeout(std::time::Duration::from_secs(5))?;
+            Ok(SqliteBackend {
+                path,
+                import_from: import_from.to_path_buf(),
+                conn,
+            })
+        }
+
+        // Whether the tasks table exists; reading it also fails early on a
+        // file that isn't a database at all
+        fn has_table(&self) -> Result<bool> {
+            let table: Option<String> = self
+                .conn
+                .query_row(
+                    "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'tasks'",
+                    [],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| TdError::Corrupt {
+                    path: self.path.clone(),
+                    reason: format!(
+                        "{}. td doctor can't repair a database SQLite can't open; restore it from a backup",
+                        e
+                    ),
+                })?;
+            Ok(table.is_some())
+        }
+
+        // The tasks from the JSON file the database was set up from
+        fn load_import(&self) -> Result<Vec<Task>> {
+            if !self.import_from.exists() {
+                return Ok(Vec::new());
+            }
+            load_tasks(&self.import_from)
+        }
+
+        // Each task row as its ID and stored JSON
+        fn rows(&self) -> Result<Vec<(i64, String)>> {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT id, data FROM tasks ORDER BY id")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            Ok(rows.collect::<rusqlite::Result<_>>()?)
+        }
+
+        fn upsert(conn: &Connection, task: &Task) -> Result<()> {
+            conn.execute(
+                "INSERT OR REPLACE INTO tasks (id, date, done, data) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    task.id as i64,
+                    task.date,
+                    task.done,
+                    serde_json::to_string(task)?
+                ],
+            )?;
+            Ok(())
+        }
+    }
+
+    impl StorageBackend for SqliteBackend {
+        fn load(&self) -> Result<Vec<Task>> {
+            if !self.has_table()? {
+                return self.load_import();
+            }
+            let mut tasks = Vec::new();
+            for (id, data) in self.rows()? {
+                let task = serde_json::from_str(&data).map_err(|e| TdError::Corrupt {
+                    path: self.path.clone(),
+                    reason: format!("task #{}: {}", id, e),
+                })?;
+                tasks.push(task);
+            }
+            Ok(tasks)
+        }
+
+        // Creating the table is part of the same transaction, so a failed
+        // first write leaves the database as it was
+        fn save(&self, tasks: &[Task]) -> Result<()> {
+            let tx = self.conn.unchecked_transaction()?;
+            tx.execute_batch(
+                "CREATE TABLE IF NOT EXISTS tasks (
+                     id INTEGER PRIMARY KEY,
+                     date TEXT NOT NULL,
+                     done INTEGER NOT NULL,
+                     data TEXT NOT NULL
+                 );
+                 CREATE INDEX IF NOT EXISTS tasks_date ON tasks (date, done);",
+            )?;
+            tx.execute("DELETE FROM tasks", [])?;
+            for task in tasks {
+                Self::upsert(&tx, task)?;
+            }
+            tx.commit()?;
+            Ok(())
+        }
+
+        fn apply(&self, tasks: &[Task], changes: &[Change]) -> Result<()> {
+            if changes.is_empty() {
+                return Ok(());
+            }
+            // The first write stores the imported tasks along with the changes
+            if !self.has_table()? {
+                return self.save(tasks);
+            }
+            let tx = self.conn.unchecked_transaction()?;
+            for change in changes {
+                match &change.after {
+                    Some(task) => Self::upsert(&tx, task)?,
+                    None => {
+                        tx.execute("DELETE FROM tasks WHERE id = ?1", [change.id as i64])?;
+                    }
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        }
+
+        // Rows whose JSON no longer reads as a task are the ones lost; a
+        // database SQLite itself can't read is beyond repair here
+        fn salvage(&self) -> Result<Option<Salvage>> {
+            if !self.has_table()? {
+                return Ok(Some(salvage_task_file(&self.import_from)?));
+            }
+            let rows = self.rows()?;
+            let count = rows.len();
+            let tasks: Vec<Task> = rows
+                .iter()
+                .filter_map(|(_, data)| serde_json::from_str(data).ok())
+                .collect();
+            Ok(Some(Salvage {
+                lost: count - tasks.len(),
+                tasks,
+            }))
+        }
+
+        fn path(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use tempfile::TempDir;
+
+        #[test]
+        fn test_sqlite_backend() {
+            let temp_dir = TempDir::new().unwrap();
+            let json_path = temp_dir.path().join("todo.json");
+            let task = |id: usize, name: &str| Task {
+                id,
+                task: name.to_string(),
+                date: "2025-08-01".to_string(),
+                ..Default::default()
+            };
+            save_tasks(&json_path, &[task(1, "Imported")]).unwrap();
+
+            // A new database picks up the existing JSON tasks
+            let db_path = temp_dir.path().join("todo.db");
+            let backend = SqliteBackend::open(db_path.clone(), &json_path).unwrap();
+            assert_eq!(backend.load().unwrap(), vec![task(1, "Imported")]);
+
+            let mut done = task(1, "Imported");
+            done.done = true;
+            let changes = vec![
+                Change {
+                    id: 1,
+                    before: Some(task(1, "Imported")),
+                    after: Some(done.clone()),
+                },
+                Change {
+                    id: 2,
+                    before: None,
+                    after: Some(task(2, "Added")),
+                },
+            ];
+            // The first write stores every task, not just the changed ones
+            backend
+                .apply(&[done.clone(), task(2, "Added")], &changes)
+                .unwrap();
+
+            // Reopening doesn't import again
+            save_tasks(&json_path, &[]).unwrap();
+            let backend = SqliteBackend::open(db_path, &json_path).unwrap();
+            assert_eq!(backend.load().unwrap(), vec![done, task(2, "Added")]);
+
+            backend
+                .apply(
+                    &[],
+                    &[Change {
+                        id: 2,
+                        before: Some(task(2, "Added")),
+                        after: None,
+                    }],
+                )
+                .unwrap();
+            assert_eq!(backend.load().unwrap().len(), 1);
+        }
+
+        #[test]
+        fn test_sqlite_read_only_and_salvage() {
+            let temp_dir = TempDir::new().unwrap();
+            let json_path = temp_dir.path().join("todo.json");
+            let db_path = temp_dir.path().join("todo.db");
+            let task = Task {
+                id: 1,
+                task: "Intact".to_string(),
+                date: "2025-08-01".to_string(),
+                ..Default::default()
+            };
+
+            // Reading doesn't create a database that doesn't exist yet
+            save_tasks(&json_path, std::slice::from_ref(&task)).unwrap();
+            let reader = SqliteBackend::open_read_only(db_path.clone(), &json_path).unwrap();
+            assert_eq!(reader.load().unwrap(), vec![task.clone()]);
+            assert!(!db_path.exists());
+
+            let writer = SqliteBackend::open(db_path.clone(), &json_path).unwrap();
+            writer.save(std::slice::from_ref(&task)).unwrap();
+            let reader = SqliteBackend::open_read_only(db_path.clone(), &json_path).unwrap();
+            assert!(reader.save(&[]).is_err());
+
+            // A row that no longer parses makes load fail, but the rest is recovered
+            let writer = SqliteBackend::open(db_path.clone(), &json_path).unwrap();
+            writer
+                .conn
+                .execute(
+                    "INSERT INTO tasks (id, date, done, data) VALUES (2, '2025-08-01', 0, '{')",
+                    [],
+                )
+                .unwrap();
+            assert!(matches!(
+                TdError::from(reader.load().unwrap_err()),
+                TdError::Corrupt { .. }
+            ));
+            let salvage = reader.salvage().unwrap().unwrap();
+            assert_eq!(salvage.tasks, vec![task.clone()]);
+            assert_eq!(salvage.lost, 1);
+        }
+
+        #[test]
+        fn test_sqlite_damaged_import_creates_nothing() {
+            let temp_dir = TempDir::new().unwrap();
+            let json_path = temp_dir.path().join("todo.json");
+            let db_path = temp_dir.path().join("todo.db");
+            let task = |id: usize| Task {
+                id,
+                task: format!("Task {}", id),
+                date: "2025-08-01".to_string(),
+                ..Default::default()
+            };
+            save_tasks(&json_path, &[task(1), task(2)]).unwrap();
+            let data = std::fs::read_to_string(&json_path).unwrap();
+            std::fs::write(&json_path, &data[..data.rfind("\"Task 2\"").unwrap()]).unwrap();
+
+            // Loading fails without setting up the table, so the import is tried again
+            let backend = SqliteBackend::open(db_path.clone(), &json_path).unwrap();
+            assert!(backend.load().is_err());
+            assert!(!backend.has_table().unwrap());
+
+            // What doctor recovers comes from the JSON file and is written in one go
+            let salvage = backend.salvage().unwrap().unwrap();
+            assert_eq!(salvage.tasks, vec![task(1)]);
+            backend.save(&salvage.tasks).unwrap();
+            let backend = SqliteBackend::open(db_path, &json_path).unwrap();
+            assert_eq!(backend.load().unwrap(), vec![task(1)]);
+        }
+    }
+}
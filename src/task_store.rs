@@ -1,10 +1,9 @@
-use crate::model::{load_tasks, save_tasks, Task};
-use anyhow::Result;
-use std::cell::RefCell;
-
-thread_local! {
-    static TASK_STORE: RefCell<Option<Vec<Task>>> = const { RefCell::new(None) };
-}
+use crate::error::{Result, TdError};
+use crate::journal::{diff_tasks, Change};
+use crate::model::{lock_storage, Salvage, StoreLock, Task, SCHEMA_VERSION};
+use crate::storage::{JsonBackend, StorageBackend};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
 // Validation function to ensure task integrity
 fn validate_tasks(tasks: Vec<Task>) -> Vec<Task> {
@@ -33,182 +32,452 @@ fn validate_tasks(tasks: Vec<Task>) -> Vec<Task> {
             continue;
         }
 
+        // A task can't be its own subtask
+        let mut task = task;
+        if task.parent == Some(task.id) {
+            eprintln!("Warning: Task #{} listed itself as its parent", task.id);
+            task.parent = None;
+        }
+
         seen_ids.insert(task.id);
         validated_tasks.push(task);
     }
 
+    break_parent_cycles(&mut validated_tasks);
+    break_dependency_cycles(&mut validated_tasks);
     validated_tasks
 }
 
-pub struct TaskStore;
+// Detach tasks whose chain of parents leads back to themselves, so walking
+// up (or cascading down) a subtask tree always ends
+fn break_parent_cycles(tasks: &mut [Task]) {
+    let parents: std::collections::HashMap<usize, Option<usize>> =
+        tasks.iter().map(|t| (t.id, t.parent)).collect();
+    let mut detached = HashSet::new();
+
+    for task in tasks.iter_mut() {
+        let mut seen = HashSet::new();
+        let mut current = task.parent;
+        while let Some(id) = current {
+            if id == task.id {
+                eprintln!(
+                    "Warning: Detaching task #{} from parent #{} to break a cycle",
+                    task.id,
+                    task.parent.unwrap_or_default()
+                );
+                task.parent = None;
+                detached.insert(task.id);
+                break;
+            }
+            // A cycle further up that this task isn't part of
+            if !seen.insert(id) || detached.contains(&id) {
+                break;
+            }
+            current = parents.get(&id).copied().flatten();
+        }
+    }
+}
+
+// Drop the `blocked_by` edges that close a dependency cycle, since no task
+// in a cycle could ever be unblocked
+fn break_dependency_cycles(tasks: &mut [Task]) {
+    let index: std::collections::HashMap<usize, usize> =
+        tasks.iter().enumerate().map(|(i, t)| (t.id, i)).collect();
+    // 0 = not visited yet, 1 = on the current path, 2 = finished
+    let mut state = vec![0u8; tasks.len()];
 
-impl TaskStore {
-    pub fn init() -> Result<()> {
-        TASK_STORE.with(|store| {
-            let tasks = match load_tasks() {
-                Ok(t) => t,
-                Err(e) => {
+    for start in 0..tasks.len() {
+        if state[start] != 0 {
+            continue;
+        }
+        state[start] = 1;
+        // Depth-first walk; each entry is (task index, next blocker to look at)
+        let mut stack = vec![(start, 0)];
+        while let Some(&(node, pos)) = stack.last() {
+            let Some(&blocker) = tasks[node].blocked_by.get(pos) else {
+                state[node] = 2;
+                stack.pop();
+                continue;
+            };
+            match index.get(&blocker) {
+                Some(&next) if state[next] == 1 => {
                     eprintln!(
-                        "Warning: Could not load tasks ({}). Initializing with empty store.",
-                        e
+                        "Warning: Dropping dependency #{} -> #{} that forms a cycle",
+                        tasks[node].id, blocker
                     );
-                    // Backup the corrupted file with a timestamp
-                    if let Ok(path) = crate::model::get_storage_path() {
-                        if path.exists() {
-                            let timestamp = chrono::Local::now().format("%Y-%m-%d-%H%M%S");
-                            let backup_filename = format!("todo.json.{}.bak", timestamp);
-                            let backup_path = path.with_file_name(backup_filename);
-                            eprintln!(
-                                "Attempting to back up corrupted task file to: {:?}",
-                                backup_path
-                            );
-                            if let Err(rename_err) = std::fs::rename(&path, &backup_path) {
-                                eprintln!(
-                                    "Error creating backup of corrupted file: {}",
-                                    rename_err
-                                );
-                            } else {
-                                eprintln!(
-                                    "Successfully created timestamped backup of corrupted file."
-                                );
-                            }
-                        }
-                    }
-                    Vec::new()
+                    tasks[node].blocked_by.remove(pos);
                 }
-            };
-            // Validate tasks to prevent potential runtime issues
-            let validated_tasks = validate_tasks(tasks);
-            *store.borrow_mut() = Some(validated_tasks);
-            Ok(())
-        })
+                Some(&next) if state[next] == 0 => {
+                    state[next] = 1;
+                    stack.last_mut().unwrap().1 += 1;
+                    stack.push((next, 0));
+                }
+                _ => stack.last_mut().unwrap().1 += 1,
+            }
+        }
     }
+}
 
-    pub fn get_all_tasks() -> Result<Vec<Task>> {
-        TASK_STORE.with(|store| {
-            let borrowed = store.borrow();
-            match borrowed.as_ref() {
-                Some(tasks) => Ok(tasks.clone()),
-                None => Err(anyhow::anyhow!("Task store not initialized")),
-            }
-        })
+// An open task store: the tasks loaded from a backend, edited in memory and
+// written back with `save`. Stores are independent, so several can be open at once.
+pub struct Store {
+    backend: Box<dyn StorageBackend>,
+    tasks: Vec<Task>,
+    // Tasks as loaded, used to work out what a command changed
+    snapshot: Vec<Task>,
+    // Tasks moved to the archive; saved, but not part of the undo history
+    archived: HashSet<usize>,
+    // Held while the store is open for writing so concurrent commands can't interleave
+    _lock: Option<StoreLock>,
+    read_only: bool,
+    // Why the stored tasks couldn't all be loaded, if they couldn't; such a
+    // store is never saved, so the data is left for `td doctor`
+    damage: Option<String>,
+}
+
+impl Store {
+    // Open the todo.json at `path` for a command that modifies tasks
+    pub fn open(path: impl Into<PathBuf>) -> Result<Store> {
+        Self::with_backend(Box::new(JsonBackend::new(path.into())), false)
     }
 
-    pub fn add_task(task: Task) -> Result<()> {
-        TASK_STORE.with(|store| {
-            let mut borrowed = store.borrow_mut();
-            if let Some(ref mut tasks) = *borrowed {
-                tasks.push(task);
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!("Task store not initialized"))
-            }
-        })
+    // Open the todo.json at `path` for reading only
+    pub fn open_read_only(path: impl Into<PathBuf>) -> Result<Store> {
+        Self::with_backend(Box::new(JsonBackend::new(path.into())), true)
     }
 
-    pub fn update_task(id: usize, updated_task: Task) -> Result<bool> {
-        TASK_STORE.with(|store| {
-            let mut borrowed = store.borrow_mut();
-            if let Some(ref mut tasks) = *borrowed {
-                if let Some(pos) = tasks.iter().position(|t| t.id == id) {
-                    tasks[pos] = updated_task;
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            } else {
-                Err(anyhow::anyhow!("Task store not initialized"))
+    // Open a store on any backend. A writable store takes the store lock
+    // before loading and holds it until dropped, so the whole
+    // load-modify-save cycle is one transaction. A read-only store takes no
+    // lock and `save` refuses to write. A writable store whose tasks
+    // couldn't all be loaded is refused, before a command changes anything.
+    pub fn with_backend(backend: Box<dyn StorageBackend>, read_only: bool) -> Result<Store> {
+        let lock = if read_only {
+            None
+        } else {
+            Some(lock_storage(backend.path())?)
+        };
+        let mut store = Store {
+            backend,
+            tasks: Vec::new(),
+            snapshot: Vec::new(),
+            archived: HashSet::new(),
+            _lock: lock,
+            read_only,
+            damage: None,
+        };
+        // Files from older versions are upgraded once, by the first command
+        // that writes; unreadable ones are left for `reload` to report
+        if !read_only {
+            if let Ok(Some(backup)) = store.backend.upgrade() {
+                eprintln!(
+                    "Upgraded {} to format version {}; the previous file was kept as {}.",
+                    store.path().display(),
+                    SCHEMA_VERSION,
+                    backup.display()
+                );
             }
-        })
+        }
+        store.reload()?;
+        if !read_only {
+            store.ensure_undamaged()?;
+        }
+        Ok(store)
     }
 
-    pub fn remove_task(id: usize) -> Result<bool> {
-        TASK_STORE.with(|store| {
-            let mut borrowed = store.borrow_mut();
-            if let Some(ref mut tasks) = *borrowed {
-                if let Some(pos) = tasks.iter().position(|t| t.id == id) {
-                    tasks.remove(pos);
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            } else {
-                Err(anyhow::anyhow!("Task store not initialized"))
-            }
+    // Lock the store for `td doctor` without loading anything; it reads the
+    // data with `load_raw` and writes it back with `replace_all`
+    pub fn open_for_repair(backend: Box<dyn StorageBackend>) -> Result<Store> {
+        let lock = lock_storage(backend.path())?;
+        Ok(Store {
+            backend,
+            tasks: Vec::new(),
+            snapshot: Vec::new(),
+            archived: HashSet::new(),
+            _lock: Some(lock),
+            read_only: false,
+            damage: None,
         })
     }
 
-    pub fn get_max_id() -> Result<usize> {
-        TASK_STORE.with(|store| {
-            let borrowed = store.borrow();
-            if let Some(tasks) = borrowed.as_ref() {
-                Ok(tasks.iter().map(|t| t.id).max().unwrap_or(0))
-            } else {
-                Err(anyhow::anyhow!("Task store not initialized"))
+    // Let other td processes write while a command waits on the user, e.g.
+    // for an editor. Changes made so far are saved first; `relock` takes the
+    // lock back and reloads what the others wrote.
+    pub fn unlock(&mut self) -> Result<()> {
+        if !self.read_only {
+            self.save()?;
+        }
+        self._lock = None;
+        Ok(())
+    }
+
+    pub fn relock(&mut self) -> Result<()> {
+        if !self.read_only {
+            self._lock = Some(lock_storage(self.path())?);
+        }
+        self.reload()?;
+        self.ensure_undamaged()
+    }
+
+    // Read the tasks again, e.g. to pick up changes made by other td processes.
+    // Unsaved changes are discarded.
+    pub fn reload(&mut self) -> Result<()> {
+        self.damage = None;
+        let tasks = match self.backend.load().map_err(TdError::from) {
+            Ok(t) => t,
+            // Data from a newer td is fine, just not readable here
+            Err(e @ TdError::UnsupportedVersion { .. }) => return Err(e),
+            Err(e) => {
+                eprintln!(
+                    "Warning: Could not load tasks ({}). Run `td doctor` to recover them.",
+                    e
+                );
+                self.damage = Some(e.to_string());
+                Vec::new()
             }
-        })
+        };
+        // Validate tasks to prevent potential runtime issues
+        let count = tasks.len();
+        self.tasks = validate_tasks(tasks);
+        if self.damage.is_none() && self.tasks.len() < count {
+            let skipped = count - self.tasks.len();
+            eprintln!(
+                "Warning: {} task(s) were skipped. Run `td doctor` to repair them.",
+                skipped
+            );
+            self.damage = Some(format!("{} task(s) could not be loaded", skipped));
+        }
+        self.snapshot = self.tasks.clone();
+        self.archived.clear();
+        Ok(())
+    }
+
+    // Fail if the last load was incomplete, since saving would drop the rest
+    fn ensure_undamaged(&self) -> Result<()> {
+        match &self.damage {
+            Some(reason) => Err(TdError::Corrupt {
+                path: self.path().to_path_buf(),
+                reason: format!(
+                    "{}. Nothing was changed; run `td doctor` to repair the task list",
+                    reason
+                ),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // The tasks exactly as stored, without the checks `reload` applies
+    pub fn load_raw(&self) -> Result<Vec<Task>> {
+        Ok(self.backend.load()?)
+    }
+
+    // What the backend can still recover after `load_raw` reported damage
+    pub fn salvage(&self) -> Result<Option<Salvage>> {
+        Ok(self.backend.salvage()?)
+    }
+
+    // Overwrite everything stored with `tasks`, after copying the current
+    // data to `backup`
+    pub fn replace_all(&mut self, tasks: Vec<Task>, backup: &Path) -> Result<()> {
+        if self.path().exists() {
+            std::fs::copy(self.path(), backup)?;
+        }
+        self.backend.save(&tasks)?;
+        self.tasks = tasks;
+        self.snapshot = self.tasks.clone();
+        self.damage = None;
+        Ok(())
+    }
+
+    // The file the backend keeps tasks in
+    pub fn path(&self) -> &Path {
+        self.backend.path()
+    }
+
+    // A file kept next to the store, such as journal.json
+    pub fn sibling_path(&self, file_name: &str) -> PathBuf {
+        self.path().with_file_name(file_name)
+    }
+
+    pub fn tasks(&self) -> &[Task] {
+        &self.tasks
+    }
+
+    pub fn find(&self, id: usize) -> Option<&Task> {
+        self.tasks.iter().find(|t| t.id == id)
+    }
+
+    pub fn max_id(&self) -> usize {
+        self.tasks.iter().map(|t| t.id).max().unwrap_or(0)
+    }
+
+    pub fn add(&mut self, task: Task) {
+        self.tasks.push(task);
     }
 
-    pub fn find_task_by_id(id: usize) -> Result<Option<Task>> {
-        TASK_STORE.with(|store| {
-            let borrowed = store.borrow();
-            if let Some(tasks) = borrowed.as_ref() {
-                Ok(tasks.iter().find(|t| t.id == id).cloned())
-            } else {
-                Err(anyhow::anyhow!("Task store not initialized"))
+    // Replace the task with ID `id`; false if there is none
+    pub fn update(&mut self, id: usize, updated_task: Task) -> bool {
+        match self.tasks.iter().position(|t| t.id == id) {
+            Some(pos) => {
+                self.tasks[pos] = updated_task;
+                true
             }
-        })
+            None => false,
+        }
     }
 
-    pub fn save_to_disk() -> Result<()> {
-        TASK_STORE.with(|store| {
-            let borrowed = store.borrow();
-            if let Some(tasks) = borrowed.as_ref() {
-                save_tasks(tasks)?;
-            } else {
-                return Err(anyhow::anyhow!("Task store not initialized"));
+    // Remove the task with ID `id`; false if there is none
+    pub fn remove(&mut self, id: usize) -> bool {
+        match self.tasks.iter().position(|t| t.id == id) {
+            Some(pos) => {
+                self.tasks.remove(pos);
+                true
             }
-            Ok(())
-        })
+            None => false,
+        }
     }
 
-    #[cfg(test)]
-    pub fn reset_store_for_testing() {
-        TASK_STORE.with(|store| {
-            *store.borrow_mut() = Some(vec![]);
-        });
+    // Drop tasks that were moved to the archive. The move is saved like any
+    // other change but left out of the undo history.
+    pub fn archive_tasks(&mut self, ids: &HashSet<usize>) {
+        self.tasks.retain(|t| !ids.contains(&t.id));
+        self.archived.extend(ids);
     }
 
-    #[allow(dead_code)] // Allow dead code in non-test builds
-    pub fn reset_for_testing_integration() {
-        #[cfg(test)]
-        TASK_STORE.with(|store| {
-            *store.borrow_mut() = Some(vec![]);
-        });
+    // Changes made since the store was loaded, archiving aside
+    pub fn pending_changes(&self) -> Vec<Change> {
+        let mut changes = self.unsaved_changes();
+        changes.retain(|c| !self.archived.contains(&c.id));
+        changes
+    }
+
+    fn unsaved_changes(&self) -> Vec<Change> {
+        diff_tasks(&self.snapshot, &self.tasks)
+    }
+
+    // Write what changed since the store was loaded or last saved. Returns
+    // the changes, archiving aside, e.g. for the undo history.
+    pub fn save(&mut self) -> Result<Vec<Change>> {
+        if self.read_only {
+            return Err(TdError::Other(anyhow::anyhow!(
+                "Task store was opened read-only"
+            )));
+        }
+        self.ensure_undamaged()?;
+        self.backend.apply(&self.tasks, &self.unsaved_changes())?;
+        let changes = self.pending_changes();
+        self.snapshot = self.tasks.clone();
+        self.archived.clear();
+        Ok(changes)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
+
     #[test]
-    fn test_task_store_init() {
-        // This test needs to be run in isolation because of the thread_local storage
-        // Reset the store for this test
-        TASK_STORE.with(|store| {
-            *store.borrow_mut() = Some(vec![]);
+    fn test_store_open_and_save() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("todo.json");
+
+        let mut store = Store::open(&path).unwrap();
+        assert!(store.tasks().is_empty());
+        store.add(Task {
+            id: 1,
+            task: "Saved".to_string(),
+            date: "2023-01-01".to_string(),
+            ..Default::default()
         });
+        assert_eq!(store.pending_changes().len(), 1);
+        assert_eq!(store.save().unwrap().len(), 1);
+        // Saved changes aren't reported again
+        assert!(store.pending_changes().is_empty());
+        assert!(store.save().unwrap().is_empty());
+        drop(store);
+
+        let store = Store::open(&path).unwrap();
+        assert_eq!(store.find(1).unwrap().task, "Saved");
+        assert!(store.pending_changes().is_empty());
+    }
 
-        let result = TaskStore::init();
-        assert!(result.is_ok());
+    #[test]
+    fn test_validate_breaks_dependency_cycles() {
+        let task = |id: usize, blocked_by: Vec<usize>| Task {
+            id,
+            task: format!("Task {}", id),
+            date: "2023-01-01".to_string(),
+            blocked_by,
+            ..Default::default()
+        };
+        // 1 -> 2 -> 3 -> 1 is a cycle, 4 blocks on itself, 5 -> 1 is fine
+        let tasks = validate_tasks(vec![
+            task(1, vec![2]),
+            task(2, vec![3]),
+            task(3, vec![1]),
+            task(4, vec![4]),
+            task(5, vec![1, 99]),
+        ]);
+
+        assert_eq!(tasks[0].blocked_by, vec![2]);
+        assert_eq!(tasks[1].blocked_by, vec![3]);
+        assert!(tasks[2].blocked_by.is_empty());
+        assert!(tasks[3].blocked_by.is_empty());
+        assert_eq!(tasks[4].blocked_by, vec![1, 99]);
     }
 
     #[test]
-    fn test_task_store_operations() {
-        // Initialize an empty store for testing
-        TASK_STORE.with(|store| {
-            *store.borrow_mut() = Some(vec![]);
+    fn test_validate_breaks_parent_cycles() {
+        let task = |id: usize, parent: Option<usize>| Task {
+            id,
+            task: format!("Task {}", id),
+            date: "2023-01-01".to_string(),
+            parent,
+            ..Default::default()
+        };
+        // 1 -> 2 -> 1 is a cycle, 3 hangs off it and 4 -> 3 is fine
+        let tasks = validate_tasks(vec![
+            task(3, Some(1)),
+            task(1, Some(2)),
+            task(2, Some(1)),
+            task(4, Some(3)),
+        ]);
+        let parents: Vec<Option<usize>> = tasks.iter().map(|t| t.parent).collect();
+        assert_eq!(parents, vec![Some(1), None, Some(1), Some(3)]);
+    }
+
+    #[test]
+    fn test_read_only_store_is_not_saved() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut store = Store::open_read_only(temp_dir.path().join("todo.json")).unwrap();
+        assert!(store.save().is_err());
+    }
+
+    #[test]
+    fn test_two_stores_at_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut personal = Store::open(temp_dir.path().join("personal").join("todo.json")).unwrap();
+        let team = Store::open(temp_dir.path().join("team").join("todo.json")).unwrap();
+
+        personal.add(Task {
+            id: 1,
+            task: "Mine".to_string(),
+            date: "2023-01-01".to_string(),
+            ..Default::default()
         });
+        assert_eq!(personal.max_id(), 1);
+        assert!(team.tasks().is_empty());
+        assert_ne!(
+            personal.sibling_path("journal.json"),
+            team.sibling_path("journal.json")
+        );
+    }
+
+    #[test]
+    fn test_task_store_operations() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut store = Store::open(temp_dir.path().join("todo.json")).unwrap();
 
         // Test adding a task
         let task = Task {
@@ -217,17 +486,15 @@ mod tests {
             date: "2023-01-01".to_string(),
             done: false,
             reuse_by: None,
+            ..Default::default()
         };
-
-        let result = TaskStore::add_task(task.clone());
-        assert!(result.is_ok());
+        store.add(task.clone());
 
         // Test getting max ID
-        let max_id = TaskStore::get_max_id().unwrap();
-        assert_eq!(max_id, 1);
+        assert_eq!(store.max_id(), 1);
 
         // Test finding the task by ID
-        let found_task = TaskStore::find_task_by_id(1).unwrap();
+        let found_task = store.find(1);
         assert!(found_task.is_some());
         assert_eq!(found_task.unwrap().task, "Test task");
 
@@ -238,39 +505,29 @@ mod tests {
             date: "2023-01-01".to_string(),
             done: true,
             reuse_by: None,
+            ..Default::default()
         };
-
-        let update_result = TaskStore::update_task(1, updated_task);
-        assert!(update_result.is_ok());
-        assert_eq!(update_result.unwrap(), true);
+        assert!(store.update(1, updated_task));
 
         // Verify the update
-        let updated_found_task = TaskStore::find_task_by_id(1).unwrap();
-        assert!(updated_found_task.is_some());
-        let task = updated_found_task.unwrap();
+        let task = store.find(1).unwrap();
         assert_eq!(task.task, "Updated task");
-        assert_eq!(task.done, true);
+        assert!(task.done);
 
         // Test removing a task
-        let remove_result = TaskStore::remove_task(1);
-        assert!(remove_result.is_ok());
-        assert_eq!(remove_result.unwrap(), true);
+        assert!(store.remove(1));
 
         // Verify the removal
-        let removed_found_task = TaskStore::find_task_by_id(1).unwrap();
-        assert!(removed_found_task.is_none());
+        assert!(store.find(1).is_none());
     }
 
     #[test]
     fn test_task_not_found() {
-        // Initialize an empty store for testing
-        TASK_STORE.with(|store| {
-            *store.borrow_mut() = Some(vec![]);
-        });
+        let temp_dir = TempDir::new().unwrap();
+        let mut store = Store::open(temp_dir.path().join("todo.json")).unwrap();
 
         // Try to find a non-existent task
-        let found_task = TaskStore::find_task_by_id(999).unwrap();
-        assert!(found_task.is_none());
+        assert!(store.find(999).is_none());
 
         // Try to update a non-existent task
         let updated_task = Task {
@@ -279,14 +536,11 @@ mod tests {
             date: "2023-01-01".to_string(),
             done: false,
             reuse_by: None,
+            ..Default::default()
         };
-        let update_result = TaskStore::update_task(999, updated_task);
-        assert!(update_result.is_ok());
-        assert_eq!(update_result.unwrap(), false); // Should return false for non-existent task
+        assert!(!store.update(999, updated_task)); // Should return false for non-existent task
 
         // Try to remove a non-existent task
-        let remove_result = TaskStore::remove_task(999);
-        assert!(remove_result.is_ok());
-        assert_eq!(remove_result.unwrap(), false); // Should return false for non-existent task
+        assert!(!store.remove(999)); // Should return false for non-existent task
     }
 }